@@ -0,0 +1,89 @@
+//! Times ministat loading several large files on one thread and then on one
+//! per CPU. Run with `cargo bench`. The total size of the inputs, 320 MB by
+//! default, is set with `MINISTAT_BENCH_MB`, and the number of files, up to
+//! ministat's limit of 7, with `MINISTAT_BENCH_FILES`.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Each generated line takes about this many bytes
+const LINE_BYTES: u64 = 14;
+
+fn setting(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Pseudo-random datapoints from a fixed xorshift generator, so every run
+/// reads the same inputs
+fn write_inputs(dir: &Path, files: u64, lines: u64) -> Vec<PathBuf> {
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    (0..files)
+        .map(|i| {
+            let path = dir.join(format!("{}.txt", i));
+            let mut w = BufWriter::new(File::create(&path).unwrap());
+            for _ in 0..lines {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                writeln!(w, "{:.9}", (state >> 24) as f64 / 1e9).unwrap();
+            }
+            path
+        })
+        .collect()
+}
+
+/// Runs ministat over the inputs with `jobs` threads, returning how long it
+/// took and what it printed
+fn run(paths: &[PathBuf], jobs: usize) -> (Duration, Vec<u8>) {
+    let start = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_ministat"))
+        .args(["-A", "--jobs", &jobs.to_string()])
+        .args(paths)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    (start.elapsed(), output.stdout)
+}
+
+fn main() {
+    let files = setting("MINISTAT_BENCH_FILES", 7).clamp(1, 7);
+    let megabytes = setting("MINISTAT_BENCH_MB", 320);
+    let lines = megabytes * 1_000_000 / LINE_BYTES / files;
+    let dir = env::temp_dir().join(format!("ministat-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths = write_inputs(&dir, files, lines);
+    let size: u64 = paths.iter().map(|p| fs::metadata(p).unwrap().len()).sum();
+
+    let jobs = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if jobs == 1 {
+        println!("Only one CPU is available, so expect no speedup");
+    }
+    // The fastest of a few runs, to discount a cold page cache
+    let best = |jobs| {
+        (0..3)
+            .map(|_| run(&paths, jobs))
+            .min_by_key(|run| run.0)
+            .unwrap()
+    };
+    let (serial, expected) = best(1);
+    let (parallel, output) = best(jobs);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(expected, output, "the output depends on --jobs");
+    println!(
+        "{} MB in {} files: {:.2?} on 1 thread, {:.2?} on {}, {:.2}x faster",
+        size / 1_000_000,
+        files,
+        serial,
+        parallel,
+        jobs,
+        serial.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
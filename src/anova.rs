@@ -0,0 +1,446 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::{Correction, Opt};
+use crate::dist::{chi2_cdf, f_cdf, normal_cdf, ptukey};
+use crate::pairwise::{adjust, correction_name};
+use crate::stats::{welch, Stats};
+use crate::t_table::T_CONFIDENCES;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FTest {
+    pub f: f64,
+    pub df1: f64,
+    pub df2: f64,
+    pub p: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KruskalWallis {
+    pub h: f64,
+    pub df: f64,
+    pub p: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostHoc {
+    /// Indices of the two datasets compared
+    pub first: usize,
+    pub second: usize,
+    /// Difference in means (Games-Howell) or mean ranks (Dunn), second minus
+    /// first
+    pub difference: f64,
+    /// Studentized range statistic (Games-Howell) or z (Dunn)
+    pub statistic: f64,
+    pub p: f64,
+}
+
+/// Classic one-way analysis of variance, assuming equal variances
+pub fn one_way_anova(stats: &[Stats]) -> FTest {
+    let k = stats.len() as f64;
+    let total_n: usize = stats.iter().map(|s| s.n).sum();
+    let grand_mean = stats.iter().map(|s| s.mean * s.n as f64).sum::<f64>() / total_n as f64;
+    let between: f64 = stats
+        .iter()
+        .map(|s| s.n as f64 * (s.mean - grand_mean).powi(2))
+        .sum();
+    let within: f64 = stats.iter().map(|s| (s.n - 1) as f64 * s.var).sum();
+    let (df1, df2) = (k - 1., total_n as f64 - k);
+    let f = (between / df1) / (within / df2);
+    FTest {
+        f,
+        df1,
+        df2,
+        p: 1. - f_cdf(f, df1, df2),
+    }
+}
+
+/// Welch's analysis of variance, which doesn't assume equal variances. It
+/// weights each dataset by the inverse of its variance, so returns `None` if
+/// any dataset has no variance.
+pub fn welch_anova(stats: &[Stats]) -> Option<FTest> {
+    if stats.iter().any(|s| s.var <= 0.) {
+        return None;
+    }
+    let k = stats.len() as f64;
+    let weights: Vec<f64> = stats.iter().map(|s| s.n as f64 / s.var).collect();
+    let total_weight: f64 = weights.iter().sum();
+    let weighted_mean = stats
+        .iter()
+        .zip(&weights)
+        .map(|(s, w)| w * s.mean)
+        .sum::<f64>()
+        / total_weight;
+    let a = stats
+        .iter()
+        .zip(&weights)
+        .map(|(s, w)| w * (s.mean - weighted_mean).powi(2))
+        .sum::<f64>()
+        / (k - 1.);
+    let tmp: f64 = stats
+        .iter()
+        .zip(&weights)
+        .map(|(s, w)| (1. - w / total_weight).powi(2) / (s.n - 1) as f64)
+        .sum();
+    let b = 1. + 2. * (k - 2.) / (k * k - 1.) * tmp;
+    let f = a / b;
+    let (df1, df2) = (k - 1., (k * k - 1.) / (3. * tmp));
+    Some(FTest {
+        f,
+        df1,
+        df2,
+        p: 1. - f_cdf(f, df1, df2),
+    })
+}
+
+/// Ranks all of the datasets together, giving tied values their average rank.
+/// Returns the ranks for each dataset, and the sum of t^3 - t over each group
+/// of t tied values, which the rank-based tests need to correct for ties.
+pub fn rank_all<T: AsRef<[f64]>>(data: &[T]) -> (Vec<Vec<f64>>, f64) {
+    let mut pooled: Vec<(f64, usize, usize)> = data
+        .iter()
+        .enumerate()
+        .flat_map(|(group, values)| {
+            values
+                .as_ref()
+                .iter()
+                .enumerate()
+                .map(move |(i, &v)| (v, group, i))
+        })
+        .collect();
+    pooled.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+    let mut ranks: Vec<Vec<f64>> = data.iter().map(|d| vec![0.; d.as_ref().len()]).collect();
+    let mut ties = 0.;
+    let mut start = 0;
+    while start < pooled.len() {
+        let mut end = start + 1;
+        while end < pooled.len() && pooled[end].0 == pooled[start].0 {
+            end += 1;
+        }
+        // Ranks start + 1 through end, inclusive
+        let rank = (start + 1 + end) as f64 / 2.;
+        for &(_, group, i) in &pooled[start..end] {
+            ranks[group][i] = rank;
+        }
+        let t = (end - start) as f64;
+        ties += t * t * t - t;
+        start = end;
+    }
+    (ranks, ties)
+}
+
+pub fn kruskal_wallis<T: AsRef<[f64]>>(data: &[T]) -> KruskalWallis {
+    let (ranks, ties) = rank_all(data);
+    let n: f64 = ranks.iter().map(|r| r.len() as f64).sum();
+    let h = 12. / (n * (n + 1.))
+        * ranks
+            .iter()
+            .map(|r| r.iter().sum::<f64>().powi(2) / r.len() as f64)
+            .sum::<f64>()
+        - 3. * (n + 1.);
+    let h = h / (1. - ties / (n * n * n - n));
+    let df = (data.len() - 1) as f64;
+    KruskalWallis {
+        h,
+        df,
+        p: 1. - chi2_cdf(h, df),
+    }
+}
+
+/// Games-Howell pairwise comparisons, which like Welch's t-test don't assume
+/// equal variances
+pub fn games_howell(stats: &[Stats]) -> Vec<PostHoc> {
+    let k = stats.len() as f64;
+    let mut rv = vec![];
+    for i in 0..stats.len() {
+        for j in i + 1..stats.len() {
+            let (val, dof) = welch(&stats[i], &stats[j]);
+            let difference = stats[j].mean - stats[i].mean;
+            let q = difference.abs() / (val / 2.).sqrt();
+            rv.push(PostHoc {
+                first: i,
+                second: j,
+                difference,
+                statistic: q,
+                p: 1. - ptukey(q, k, dof),
+            });
+        }
+    }
+    rv
+}
+
+/// Dunn's pairwise comparisons of mean ranks, with p-values adjusted by
+/// `correction`
+pub fn dunn<T: AsRef<[f64]>>(data: &[T], correction: Correction) -> Vec<PostHoc> {
+    let (ranks, ties) = rank_all(data);
+    let n: f64 = ranks.iter().map(|r| r.len() as f64).sum();
+    let mean_ranks: Vec<f64> = ranks
+        .iter()
+        .map(|r| r.iter().sum::<f64>() / r.len() as f64)
+        .collect();
+    let variance = n * (n + 1.) / 12. - ties / (12. * (n - 1.));
+    let mut rv = vec![];
+    for i in 0..data.len() {
+        for j in i + 1..data.len() {
+            let (ni, nj) = (ranks[i].len() as f64, ranks[j].len() as f64);
+            let difference = mean_ranks[j] - mean_ranks[i];
+            let z = difference / (variance * (1. / ni + 1. / nj)).sqrt();
+            let p = 2. * (1. - normal_cdf(z.abs()));
+            rv.push(PostHoc {
+                first: i,
+                second: j,
+                difference,
+                statistic: z,
+                p,
+            });
+        }
+    }
+    let adjusted = adjust(&rv.iter().map(|r| r.p).collect::<Vec<_>>(), correction);
+    for (result, p) in rv.iter_mut().zip(adjusted) {
+        result.p = p;
+    }
+    rv
+}
+
+pub fn print_omnibus<W, T>(
+    f: &mut W,
+    stats: &[Stats],
+    data: &[T],
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+    T: AsRef<[f64]>,
+{
+    if stats.len() < 2 {
+        writeln!(f, "Omnibus tests need at least 2 datasets")?;
+        return Ok(());
+    }
+    let confidence_label = T_CONFIDENCES[opt.confidence_level.0];
+    let alpha = 1. - opt.confidence_level.level();
+    // With no spread within any dataset, or across all of them, the tests'
+    // statistics are 0 / 0
+    let anova = stats
+        .iter()
+        .any(|s| s.var > 0.)
+        .then(|| one_way_anova(stats));
+    let welch = welch_anova(stats);
+    let tied = stats
+        .iter()
+        .all(|s| s.min == s.max && s.min == stats[0].min);
+    let kw = (!tied).then(|| kruskal_wallis(data));
+    let verdict = |p: f64| {
+        if p < alpha {
+            "difference"
+        } else {
+            "no difference proven"
+        }
+    };
+
+    writeln!(
+        f,
+        "Omnibus tests across {} datasets at {}% confidence",
+        stats.len(),
+        confidence_label
+    )?;
+    match anova {
+        Some(anova) => writeln!(
+            f,
+            "\tOne-way ANOVA:  F({}, {}) = {:.6}, p = {:.6}: {}",
+            anova.df1,
+            anova.df2,
+            anova.f,
+            anova.p,
+            verdict(anova.p)
+        )?,
+        None => writeln!(
+            f,
+            "\tOne-way ANOVA:  unavailable, as no dataset has any variance"
+        )?,
+    }
+    match welch {
+        Some(welch) => writeln!(
+            f,
+            "\tWelch's ANOVA:  F({}, {:.2}) = {:.6}, p = {:.6}: {}",
+            welch.df1,
+            welch.df2,
+            welch.f,
+            welch.p,
+            verdict(welch.p)
+        )?,
+        None => writeln!(
+            f,
+            "\tWelch's ANOVA:  unavailable, as a dataset has no variance"
+        )?,
+    }
+    let welch_p = welch.map_or(1., |welch| welch.p);
+    match kw {
+        Some(kw) => writeln!(
+            f,
+            "\tKruskal-Wallis: H({}) = {:.6}, p = {:.6}: {}",
+            kw.df,
+            kw.h,
+            kw.p,
+            verdict(kw.p)
+        )?,
+        None => writeln!(
+            f,
+            "\tKruskal-Wallis: unavailable, as every datapoint is tied"
+        )?,
+    }
+    let kw_p = kw.map_or(1., |kw| kw.p);
+
+    let print_post_hoc = |f: &mut W, heading: &str, label: &str, results: Vec<PostHoc>| {
+        writeln!(f, "{}", heading)?;
+        for result in results {
+            writeln!(
+                f,
+                "\t{} vs {}: {:13.6} ({} = {:.6}, p = {:.6}){}",
+                symbols[result.first + 1],
+                symbols[result.second + 1],
+                result.difference,
+                label,
+                result.statistic,
+                result.p,
+                if result.p < alpha { " *" } else { "" }
+            )?;
+        }
+        Ok::<(), Error>(())
+    };
+    if welch_p < alpha && opt.log {
+        // Differences in the means of logs are ratios of geometric means
+        let ratios = games_howell(stats)
+            .into_iter()
+            .map(|result| PostHoc {
+                difference: result.difference.exp(),
+                ..result
+            })
+            .collect();
+        print_post_hoc(
+            f,
+            "Games-Howell post-hoc comparisons of geometric means (ratios)",
+            "q",
+            ratios,
+        )?;
+    } else if welch_p < alpha {
+        print_post_hoc(
+            f,
+            "Games-Howell post-hoc comparisons of means",
+            "q",
+            games_howell(stats),
+        )?;
+    }
+    if kw_p < alpha {
+        print_post_hoc(
+            f,
+            &format!(
+                "Dunn post-hoc comparisons of mean ranks ({} corrected)",
+                correction_name(opt.correction)
+            ),
+            "z",
+            dunn(data, opt.correction),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        dunn, games_howell, kruskal_wallis, one_way_anova, print_omnibus, rank_all, welch_anova,
+    };
+    use crate::args::Correction;
+    use crate::args::Opt;
+    use crate::plot::CLASSIC_SYMBOLS;
+    use crate::stats::Stats;
+
+    fn close(a: f64, b: f64, tol: f64) -> bool {
+        (a - b).abs() <= tol
+    }
+
+    #[test]
+    fn test_rank_all() {
+        let (ranks, ties) = rank_all(&[vec![1., 2., 2.], vec![2., 5.]]);
+        assert_eq!(vec![vec![1., 3., 3.], vec![3., 5.]], ranks);
+        assert_eq!(24., ties);
+    }
+
+    #[test]
+    fn test_omnibus() {
+        let data = [
+            vec![1., 2., 3., 4., 5.],
+            vec![3., 4., 5., 6., 7.],
+            vec![6., 7., 8., 9., 10.],
+        ];
+        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(d)).collect();
+
+        // Means 3, 5 and 8 about a grand mean of 16/3, each with variance 2.5
+        let anova = one_way_anova(&stats);
+        assert!(close(
+            anova.f,
+            (5. * (49. + 1. + 64.) / 9. / 2.) / 2.5,
+            1e-12
+        ));
+        assert_eq!((2., 12.), (anova.df1, anova.df2));
+        assert!(anova.p < 0.01);
+
+        // With equal sizes and variances, Welch's F is the classic one shrunk
+        // by 1 + 2(k - 2)/(k^2 - 1) * 3(1 - 1/k)^2/(n - 1)
+        let welch = welch_anova(&stats).unwrap();
+        assert!(close(welch.f, anova.f / (1. + 1. / 12.), 1e-12));
+        assert!(close(welch.df2, 8., 1e-12));
+        let constant = Stats::from_dataset(&[4., 4., 4.]);
+        assert!(welch_anova(&[stats[0].clone(), constant]).is_none());
+
+        let kw = kruskal_wallis(&data);
+        assert_eq!(2., kw.df);
+        assert!(kw.p < 0.05);
+
+        let gh = games_howell(&stats);
+        assert_eq!(3, gh.len());
+        assert_eq!((0, 2), (gh[1].first, gh[1].second));
+        assert_eq!(5., gh[1].difference);
+        assert!(gh[1].p < 0.01 && gh[0].p > 0.05);
+
+        let dunn = dunn(&data, Correction::Bonferroni);
+        assert!(dunn[1].p < 0.05 && dunn[0].p > 0.05);
+
+        // A single dataset has nothing to compare against
+        let mut buf = vec![];
+        print_omnibus(
+            &mut buf,
+            &stats[..1],
+            &data[..1],
+            &Opt::default(),
+            &CLASSIC_SYMBOLS,
+        )
+        .unwrap();
+        assert_eq!(
+            "Omnibus tests need at least 2 datasets\n",
+            std::str::from_utf8(&buf).unwrap()
+        );
+
+        // Nor do datasets with no spread, which would give NaN statistics
+        let constant = [vec![4., 4., 4., 4.], vec![4., 4., 4., 4.]];
+        let stats: Vec<_> = constant.iter().map(|d| Stats::from_dataset(d)).collect();
+        let mut buf = vec![];
+        print_omnibus(
+            &mut buf,
+            &stats,
+            &constant,
+            &Opt::default(),
+            &CLASSIC_SYMBOLS,
+        )
+        .unwrap();
+        assert_eq!(
+            "Omnibus tests across 2 datasets at 95% confidence
+\tOne-way ANOVA:  unavailable, as no dataset has any variance
+\tWelch's ANOVA:  unavailable, as a dataset has no variance
+\tKruskal-Wallis: unavailable, as every datapoint is tied
+",
+            std::str::from_utf8(&buf).unwrap()
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use crate::err::*;
+use crate::stats::DEFAULT_TRIM;
 use crate::t_table::T_CONFIDENCES;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 use std::str::FromStr;
 
@@ -54,6 +56,287 @@ pub struct Opt {
 
     #[clap(long = "symbols")]
     pub symbols: Option<String>,
+
+    #[clap(long = "test", default_value = "welch")]
+    /// Test used to compare each dataset against the baseline. Possible values
+    /// are welch, ks (two-sample Kolmogorov-Smirnov) and permutation.
+    pub test: Test,
+
+    #[clap(long = "bootstrap")]
+    /// Report bootstrap confidence intervals for the mean, median and any
+    /// requested percentiles, using this many resamples.
+    pub bootstrap: Option<NonZeroUsize>,
+
+    #[clap(long = "bootstrap-ci", default_value = "bca")]
+    /// How to build bootstrap confidence intervals. Possible values are bca
+    /// (bias-corrected and accelerated) and percentile.
+    pub bootstrap_ci: BootstrapCi,
+
+    #[clap(long = "percentiles", default_value = "")]
+    /// Comma-separated list of percentiles to report, e.g. 50,90,99,99.9
+    pub percentiles: Percentiles,
+
+    #[clap(long = "quantile-type", default_value = "7")]
+    /// Sample quantile definition used for percentiles, numbered 1 to 9 as in
+    /// R's quantile() (Hyndman & Fan, 1996). The default, type 7, interpolates
+    /// linearly between order statistics.
+    pub quantile_type: QuantileType,
+
+    #[clap(long = "permutations", default_value = "10000")]
+    /// Number of random permutations for the permutation test. If there are
+    /// fewer possible arrangements than this, all of them are tried instead.
+    pub permutations: NonZeroUsize,
+
+    #[clap(long = "permutation-statistic", default_value = "mean")]
+    /// Difference tested by the permutation test. Possible values are mean
+    /// and median.
+    pub permutation_statistic: Location,
+
+    #[clap(long = "robust")]
+    /// Report the median and a robust measure of spread (see --robust-spread)
+    /// instead of the mean and standard deviation, in both the table and the
+    /// plot. The table also shows trimmed and winsorised means.
+    pub robust: bool,
+
+    #[clap(long = "robust-spread", default_value = "mad")]
+    /// Spread used by --robust. Possible values are mad (median absolute
+    /// deviation, scaled to match the standard deviation for normal data) and
+    /// iqr (interquartile range).
+    pub robust_spread: Spread,
+
+    #[clap(long = "trim", default_value = "0.1")]
+    /// Fraction of the data to cut from each end for trimmed and winsorised
+    /// means, from 0 up to (but not including) 0.5.
+    pub trim: Trim,
+
+    #[clap(long = "outliers")]
+    /// Flag outliers, which are counted in the table and drawn with a distinct
+    /// glyph. Possible values are tukey (outside Q1 - k*IQR and Q3 + k*IQR),
+    /// mad (more than k*MAD from the median) and grubbs (repeated Grubbs'
+    /// tests at the chosen confidence).
+    pub outliers: Option<OutlierMethod>,
+
+    #[clap(long = "outlier-k")]
+    /// Multiplier for --outliers tukey (default 1.5) or mad (default 3).
+    pub outlier_k: Option<OutlierK>,
+
+    #[clap(long = "drop-outliers", requires = "outliers")]
+    /// Remove flagged outliers before computing statistics and comparisons.
+    pub drop_outliers: bool,
+
+    #[clap(long = "min-effect", default_value = "0")]
+    /// Smallest absolute effect size (Hedges' g, or Cliff's delta for rank
+    /// based tests) worth reporting as a difference. Significant differences
+    /// smaller than this are reported as having no practical difference.
+    pub min_effect: f64,
+
+    #[clap(long = "equivalence")]
+    /// Test whether each dataset's mean is equivalent to the baseline's, using
+    /// two one-sided Welch tests. The margin is either absolute, or relative
+    /// to the baseline's mean when given as a percentage (e.g. 2%).
+    pub equivalence: Option<Margin>,
+
+    #[clap(long = "omnibus")]
+    /// Test whether any dataset differs from the others using one-way ANOVA,
+    /// Welch's ANOVA and Kruskal-Wallis, followed by Games-Howell and Dunn
+    /// post-hoc comparisons when these are significant.
+    pub omnibus: bool,
+
+    #[clap(long = "pairwise")]
+    /// Compare every pair of datasets with Welch's t-test, and print matrices
+    /// of the differences in means and the corrected p-values.
+    pub pairwise: bool,
+
+    #[clap(long = "correction", default_value = "holm")]
+    /// Multiple-comparison correction for --pairwise and Dunn's post-hoc
+    /// comparisons. Possible values are bonferroni, holm and bh
+    /// (Benjamini-Hochberg, which controls the false discovery rate instead).
+    pub correction: Correction,
+
+    #[clap(long = "baseline")]
+    /// The dataset the others are compared against, given as its position
+    /// on the command line (starting from 1) or its file name. Defaults to
+    /// the first dataset.
+    pub baseline: Option<Baseline>,
+
+    #[clap(long = "test-variance")]
+    /// Compare each dataset's spread to the baseline's with an F-test,
+    /// Levene's test and the Brown-Forsythe test, and add a coefficient of
+    /// variation column to the table.
+    pub test_variance: bool,
+
+    #[clap(long = "power")]
+    /// Report the smallest difference from the baseline's mean that Welch's
+    /// t-test would detect with the chosen power, given the current sample
+    /// sizes and variances.
+    pub power: bool,
+
+    #[clap(long = "power-level", default_value = "80")]
+    /// Power, in percent, for --power.
+    pub power_level: Power,
+
+    #[clap(long = "target-effect", requires = "power")]
+    /// Also report how many datapoints each dataset would need for the
+    /// difference to be detected with the chosen power, given either as an
+    /// absolute difference or as a percentage of the baseline's mean (e.g. 1%).
+    pub target_effect: Option<Margin>,
+
+    #[clap(long = "normality")]
+    /// Test each dataset for normality, with Shapiro-Wilk for up to 5000
+    /// datapoints and Anderson-Darling beyond that, and report its skewness
+    /// and excess kurtosis. Datasets that are far from normal get a warning.
+    pub normality: bool,
+
+    #[clap(long = "median-ci")]
+    /// Report a distribution-free confidence interval for each dataset's
+    /// median, from its order statistics, and mark it on the plot.
+    pub median_ci: bool,
+
+    #[clap(long = "against", allow_hyphen_values = true)]
+    /// Test whether each dataset's location differs from this reference
+    /// value, e.g. an SLO threshold, and report a confidence interval for it.
+    pub against: Option<f64>,
+
+    #[clap(long = "against-test", default_value = "t", requires = "against")]
+    /// Which test --against uses. Possible values are t (one-sample t-test
+    /// on the mean), sign (sign test on the median) and wilcoxon (Wilcoxon
+    /// signed-rank test).
+    pub against_test: AgainstTest,
+
+    #[clap(long = "trend")]
+    /// Look for drift over the order in which each dataset's datapoints were
+    /// read, with a Mann-Kendall trend test, Sen's slope and change points
+    /// in the mean, and warn about datasets that aren't stationary.
+    pub trend: bool,
+
+    #[clap(long = "autocorrelation")]
+    /// Check whether each dataset's datapoints are independent of the ones
+    /// read before them, with their autocorrelations, a Ljung-Box test and
+    /// an effective sample size. Warns when the effective size is less than
+    /// half of the actual size.
+    pub autocorrelation: bool,
+
+    #[clap(long = "lags", default_value = "10", requires = "autocorrelation")]
+    /// Number of lags reported by --autocorrelation and tested by Ljung-Box.
+    pub lags: NonZeroUsize,
+
+    #[clap(long = "effective-n")]
+    /// Use each dataset's effective sample size, allowing for
+    /// autocorrelation, in place of its actual size in Welch's t-test.
+    pub effective_n: bool,
+
+    #[clap(long = "kde")]
+    /// Estimate each dataset's density with a Gaussian kernel, and report
+    /// its modes and bimodality coefficient. Datasets with several clear
+    /// peaks get a warning.
+    pub kde: bool,
+
+    #[clap(long = "bandwidth", default_value = "silverman", requires = "kde")]
+    /// Rule of thumb for the --kde bandwidth. Possible values are silverman
+    /// and scott.
+    pub bandwidth: Bandwidth,
+
+    #[clap(long = "streaming")]
+    /// Read each dataset in a single pass without storing its datapoints, for
+    /// inputs too large for memory. Large files are read in chunks in
+    /// parallel, whose summaries are merged. The moments are exact, while
+    /// quantiles come from a KLL sketch, whose rank error has stayed within
+    /// 1.65% of n in testing, and the plot is drawn from histograms. Options
+    /// that need every datapoint are not available.
+    pub streaming: bool,
+
+    #[clap(long = "jobs")]
+    /// Number of threads to read files and compute their statistics with.
+    /// Defaults to one per CPU. The output is the same for any number.
+    pub jobs: Option<NonZeroUsize>,
+
+    #[clap(long = "log")]
+    /// Analyse the natural logarithms of the data, which suits log-normal
+    /// data such as timings. The table reports geometric means and geometric
+    /// standard deviation factors, Welch's t-test and the other comparisons
+    /// of means work on the logs and report ratios, and the plot uses a
+    /// logarithmic axis. --ratio and --bootstrap still describe the original
+    /// data, and margins for --equivalence and --target-effect must be
+    /// percentages.
+    pub log: bool,
+
+    #[clap(long = "ratio")]
+    /// Report the ratio of each dataset's mean to the baseline's, and the
+    /// ratio of their geometric means for positive data.
+    pub ratio: bool,
+
+    #[clap(long = "ratio-ci", default_value = "fieller")]
+    /// How to build confidence intervals for --ratio. Possible values are
+    /// fieller and delta (the delta method's normal approximation).
+    pub ratio_ci: RatioCi,
+
+    #[clap(long = "seed", default_value = "0")]
+    /// Seed for the random number generator used for resampling.
+    pub seed: u64,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Compare two datasets as their samples arrive, and report after each
+    /// batch whether there is enough evidence to stop collecting. Uses an
+    /// always-valid confidence sequence for the difference in means, so it
+    /// can be checked any number of times. Stops on a difference, or on
+    /// equivalence within --equivalence if given.
+    Sequential(SequentialOpt),
+    /// Measure how one column of a file varies with another, with Pearson
+    /// and Spearman correlations and a least-squares line, drawn over a
+    /// scatter plot.
+    Correlate(CorrelateOpt),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SequentialOpt {
+    #[clap(long = "batch", default_value = "10")]
+    /// Number of new datapoints each dataset needs between reports.
+    pub batch: NonZeroUsize,
+
+    #[clap(short = 'f', long = "follow")]
+    /// Keep waiting for more data to be appended to the files, like tail -f,
+    /// until there is enough evidence to stop.
+    pub follow: bool,
+
+    #[clap(parse(from_os_str), max_values = 2)]
+    /// The two files to compare. Without them, each line of standard input
+    /// holds the dataset's number (1 or 2) followed by a datapoint.
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct CorrelateOpt {
+    #[clap(short = 'x', default_value = "1")]
+    /// Column holding the independent variable, plotted across.
+    pub x: Column,
+
+    #[clap(short = 'y', default_value = "2")]
+    /// Column holding the dependent variable, plotted upwards.
+    pub y: Column,
+
+    #[clap(long = "height", default_value = "20")]
+    /// Height of the scatter plot in rows.
+    pub height: u16,
+
+    #[clap(parse(from_os_str))]
+    /// The file to read. Without it, standard input is used.
+    pub file: Option<PathBuf>,
+}
+
+impl Opt {
+    /// The spread drawn around each dataset's centre
+    pub fn spread(&self) -> Spread {
+        if self.robust {
+            self.robust_spread
+        } else {
+            Spread::Stddev
+        }
+    }
 }
 
 impl Default for Opt {
@@ -65,11 +348,52 @@ impl Default for Opt {
             modern_chars: false,
             stack: false,
             column: Column(1),
-            confidence_level: Confidence(95),
+            // Confidence holds an index into T_CONFIDENCES; 2 is 95%
+            confidence_level: Confidence(2),
             delimiter: " \t".into(),
             width: None,
             files: vec![],
             symbols: None,
+            test: Test::Welch,
+            bootstrap: None,
+            bootstrap_ci: BootstrapCi::Bca,
+            percentiles: Percentiles(vec![]),
+            quantile_type: QuantileType(7),
+            permutations: NonZeroUsize::new(10000).unwrap(),
+            permutation_statistic: Location::Mean,
+            robust: false,
+            robust_spread: Spread::Mad,
+            trim: Trim(DEFAULT_TRIM),
+            outliers: None,
+            outlier_k: None,
+            drop_outliers: false,
+            min_effect: 0.,
+            equivalence: None,
+            omnibus: false,
+            pairwise: false,
+            correction: Correction::Holm,
+            baseline: None,
+            test_variance: false,
+            power: false,
+            power_level: Power(0.8),
+            target_effect: None,
+            normality: false,
+            median_ci: false,
+            against: None,
+            against_test: AgainstTest::T,
+            trend: false,
+            autocorrelation: false,
+            lags: NonZeroUsize::new(10).unwrap(),
+            effective_n: false,
+            kde: false,
+            streaming: false,
+            jobs: None,
+            bandwidth: Bandwidth::Silverman,
+            log: false,
+            ratio: false,
+            ratio_ci: RatioCi::Fieller,
+            seed: 0,
+            command: None,
         }
     }
 }
@@ -102,3 +426,304 @@ impl FromStr for Confidence {
             .map(Confidence)
     }
 }
+
+impl Confidence {
+    /// The confidence level as a fraction, e.g. 0.95
+    pub fn level(&self) -> f64 {
+        T_CONFIDENCES[self.0].parse::<f64>().unwrap() / 100.
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Test {
+    Welch,
+    Ks,
+    Permutation,
+}
+impl FromStr for Test {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "welch" => Ok(Test::Welch),
+            "ks" => Ok(Test::Ks),
+            "permutation" => Ok(Test::Permutation),
+            _ => Err(MinistatFailure::InvalidTest {
+                provided_test: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootstrapCi {
+    Bca,
+    Percentile,
+}
+impl FromStr for BootstrapCi {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bca" => Ok(BootstrapCi::Bca),
+            "percentile" => Ok(BootstrapCi::Percentile),
+            _ => Err(MinistatFailure::InvalidBootstrapCi {
+                provided_method: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Mean,
+    Median,
+}
+impl FromStr for Location {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mean" => Ok(Location::Mean),
+            "median" => Ok(Location::Median),
+            _ => Err(MinistatFailure::InvalidLocation {
+                provided_location: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Percentiles(pub Vec<f64>);
+impl FromStr for Percentiles {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|p| !p.is_empty())
+            .map(|p| match p.parse::<f64>() {
+                Ok(v) if (0. ..=100.).contains(&v) => Ok(v),
+                _ => Err(MinistatFailure::InvalidPercentile {
+                    provided_percentile: p.to_string(),
+                }),
+            })
+            .collect::<Result<_, _>>()
+            .map(Percentiles)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuantileType(pub u8);
+impl FromStr for QuantileType {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<u8>() {
+            Ok(t) if (1..=9).contains(&t) => Ok(QuantileType(t)),
+            _ => Err(MinistatFailure::InvalidQuantileType {
+                provided_type: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spread {
+    Stddev,
+    Mad,
+    Iqr,
+}
+impl FromStr for Spread {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mad" => Ok(Spread::Mad),
+            "iqr" => Ok(Spread::Iqr),
+            _ => Err(MinistatFailure::InvalidSpread {
+                provided_spread: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trim(pub f64);
+impl FromStr for Trim {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(t) if (0. ..0.5).contains(&t) => Ok(Trim(t)),
+            _ => Err(MinistatFailure::InvalidTrim {
+                provided_trim: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// Statistical power, as a fraction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Power(pub f64);
+impl FromStr for Power {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(p) if p > 0. && p < 100. => Ok(Power(p / 100.)),
+            _ => Err(MinistatFailure::InvalidPower {
+                provided_power: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutlierMethod {
+    Tukey,
+    Mad,
+    Grubbs,
+}
+impl FromStr for OutlierMethod {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tukey" => Ok(OutlierMethod::Tukey),
+            "mad" => Ok(OutlierMethod::Mad),
+            "grubbs" => Ok(OutlierMethod::Grubbs),
+            _ => Err(MinistatFailure::InvalidOutlierMethod {
+                provided_method: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// Multiplier of the spread for the outlier fences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutlierK(pub f64);
+impl FromStr for OutlierK {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(k) if k.is_finite() && k > 0. => Ok(OutlierK(k)),
+            _ => Err(MinistatFailure::InvalidOutlierK {
+                provided_k: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Correction {
+    Bonferroni,
+    Holm,
+    /// Benjamini-Hochberg
+    Bh,
+}
+impl FromStr for Correction {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bonferroni" => Ok(Correction::Bonferroni),
+            "holm" => Ok(Correction::Holm),
+            "bh" => Ok(Correction::Bh),
+            _ => Err(MinistatFailure::InvalidCorrection {
+                provided_correction: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RatioCi {
+    Fieller,
+    Delta,
+}
+impl FromStr for RatioCi {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fieller" => Ok(RatioCi::Fieller),
+            "delta" => Ok(RatioCi::Delta),
+            _ => Err(MinistatFailure::InvalidRatioCi {
+                provided_method: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bandwidth {
+    Silverman,
+    Scott,
+}
+impl FromStr for Bandwidth {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "silverman" => Ok(Bandwidth::Silverman),
+            "scott" => Ok(Bandwidth::Scott),
+            _ => Err(MinistatFailure::InvalidBandwidth {
+                provided_bandwidth: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgainstTest {
+    T,
+    Sign,
+    Wilcoxon,
+}
+impl FromStr for AgainstTest {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "t" => Ok(AgainstTest::T),
+            "sign" => Ok(AgainstTest::Sign),
+            "wilcoxon" => Ok(AgainstTest::Wilcoxon),
+            _ => Err(MinistatFailure::InvalidAgainstTest {
+                provided_test: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Baseline {
+    /// Position on the command line, starting from 1
+    Index(usize),
+    /// File name, or path as given
+    Label(String),
+}
+impl FromStr for Baseline {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(0) => Err(MinistatFailure::InvalidBaseline {
+                provided_baseline: s.to_string(),
+            }),
+            Ok(idx) => Ok(Baseline::Index(idx)),
+            Err(_) if s.is_empty() => Err(MinistatFailure::InvalidBaseline {
+                provided_baseline: s.to_string(),
+            }),
+            Err(_) => Ok(Baseline::Label(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Margin {
+    Absolute(f64),
+    /// A percentage of the baseline mean
+    Relative(f64),
+}
+impl FromStr for Margin {
+    type Err = MinistatFailure;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, relative) = match s.strip_suffix('%') {
+            Some(percent) => (percent, true),
+            None => (s, false),
+        };
+        match value.parse::<f64>() {
+            Ok(m) if m > 0. && m.is_finite() && relative => Ok(Margin::Relative(m)),
+            Ok(m) if m > 0. && m.is_finite() => Ok(Margin::Absolute(m)),
+            _ => Err(MinistatFailure::InvalidMargin {
+                provided_margin: s.to_string(),
+            }),
+        }
+    }
+}
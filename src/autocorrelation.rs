@@ -0,0 +1,135 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::Opt;
+use crate::dist::chi2_cdf;
+
+/// A warning is printed when the effective sample size is below this
+/// fraction of the actual one
+const WARNING_RATIO: f64 = 0.5;
+
+/// Autocorrelations of a sequence at lags 1 through `lags`, or none if the
+/// sequence doesn't vary
+pub fn autocorrelations(sequence: &[f64], lags: usize) -> Vec<f64> {
+    let n = sequence.len();
+    let mean = sequence.iter().sum::<f64>() / n as f64;
+    let deviations: Vec<f64> = sequence.iter().map(|x| x - mean).collect();
+    let variance: f64 = deviations.iter().map(|d| d * d).sum();
+    if variance <= 0. {
+        return vec![];
+    }
+    (1..=lags.min(n - 1))
+        .map(|lag| {
+            let covariance: f64 = deviations
+                .iter()
+                .zip(&deviations[lag..])
+                .map(|(a, b)| a * b)
+                .sum();
+            covariance / variance
+        })
+        .collect()
+}
+
+/// The Ljung-Box statistic Q for the given autocorrelations of `n`
+/// datapoints, and its p-value for there being no autocorrelation
+pub fn ljung_box(autocorrelations: &[f64], n: usize) -> (f64, f64) {
+    let n = n as f64;
+    let q = n
+        * (n + 2.)
+        * autocorrelations
+            .iter()
+            .enumerate()
+            .map(|(i, r)| r * r / (n - (i + 1) as f64))
+            .sum::<f64>();
+    (q, 1. - chi2_cdf(q, autocorrelations.len() as f64))
+}
+
+/// The number of independent datapoints that would estimate the mean as
+/// precisely as this sequence does, n / (1 + 2 * sum of autocorrelations),
+/// summing them up to the first that isn't positive, and over at most a
+/// quarter of the sequence. It is kept between 2 and n, so negative
+/// autocorrelation never claims more precision than independence would.
+pub fn effective_n(sequence: &[f64]) -> f64 {
+    let n = sequence.len();
+    let positive: f64 = autocorrelations(sequence, n / 4)
+        .into_iter()
+        .take_while(|&r| r > 0.)
+        .sum();
+    (n as f64 / (1. + 2. * positive)).clamp(2., n as f64)
+}
+
+pub fn print_autocorrelation<W, T>(
+    f: &mut W,
+    sequences: &[T],
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+    T: AsRef<[f64]>,
+{
+    for (&symbol, sequence) in symbols.iter().skip(1).zip(sequences) {
+        let sequence = sequence.as_ref();
+        let n = sequence.len();
+        let acf = autocorrelations(sequence, opt.lags.get());
+        if acf.is_empty() {
+            writeln!(f, "{} Autocorrelation: no variation to test", symbol)?;
+            continue;
+        }
+        writeln!(f, "{} Autocorrelation over sample order", symbol)?;
+        writeln!(
+            f,
+            "\tLags 1-{}: {}",
+            acf.len(),
+            acf.iter()
+                .map(|r| format!("{:.6}", r))
+                .collect::<Vec<_>>()
+                .join(" ")
+        )?;
+        let (q, p) = ljung_box(&acf, n);
+        writeln!(f, "\tLjung-Box Q({}) = {:.6}, p = {:.6}", acf.len(), q, p)?;
+        let effective = effective_n(sequence);
+        writeln!(f, "\tEffective n = {:.1} of {}", effective, n)?;
+        if effective < WARNING_RATIO * n as f64 && !opt.effective_n {
+            writeln!(
+                f,
+                "\tWarning: {}'s datapoints are autocorrelated, so the tests overstate their \
+                 confidence; consider --effective-n",
+                symbol
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{autocorrelations, effective_n, ljung_box};
+
+    #[test]
+    fn test_autocorrelation() {
+        // acf(1:10)
+        let data: Vec<f64> = (1..=10).map(|x| x as f64).collect();
+        let acf = autocorrelations(&data, 3);
+        assert!((acf[0] - 0.7).abs() < 1e-12);
+        assert!((acf[1] - 0.4121212).abs() < 1e-7);
+        assert!((acf[2] - 0.1484848).abs() < 1e-7);
+        // Box.test(1:10, lag = 3, type = "Ljung-Box")
+        let (q, p) = ljung_box(&acf, 10);
+        assert!((q - 9.458953).abs() < 1e-6);
+        assert!((p - 0.02377206).abs() < 1e-7);
+
+        // Alternating data is negatively autocorrelated, which doesn't raise
+        // the effective n above n, while slowly wandering data lowers it
+        let alternating: Vec<f64> = (0..100).map(|x| (x % 2) as f64).collect();
+        assert_eq!(100., effective_n(&alternating));
+        let wandering: Vec<f64> = (0..100).map(|x| ((x / 10) % 2) as f64).collect();
+        assert!(effective_n(&wandering) < 20.);
+
+        // Constant data has no autocorrelation to measure
+        let constant = [3.; 20];
+        assert!(autocorrelations(&constant, 5).is_empty());
+        assert_eq!(20., effective_n(&constant));
+    }
+}
@@ -0,0 +1,379 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::{BootstrapCi, Opt, QuantileType};
+use crate::dist::{normal_cdf, normal_quantile};
+use crate::rng::Rng;
+use crate::stats::{quantile, quantile_by, quantile_with};
+use crate::t_table::T_CONFIDENCES;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Statistic {
+    Mean,
+    Median,
+    /// A percentile, from 0 to 100
+    Percentile(f64, QuantileType),
+}
+
+impl Statistic {
+    /// Computes the statistic over sorted data
+    pub fn compute(&self, sorted: &[f64]) -> f64 {
+        match *self {
+            Statistic::Mean => sorted.iter().sum::<f64>() / sorted.len() as f64,
+            Statistic::Median => quantile(sorted, 0.5),
+            Statistic::Percentile(p, qtype) => quantile_with(sorted, p / 100., qtype),
+        }
+    }
+
+    /// Computes the statistic over sorted data with `sorted[skip]` left out,
+    /// without copying the data. `sum` is the sum of all of `sorted`.
+    fn compute_without(&self, sorted: &[f64], skip: usize, sum: f64) -> f64 {
+        let (p, qtype) = match *self {
+            Statistic::Mean => return (sum - sorted[skip]) / (sorted.len() - 1) as f64,
+            Statistic::Median => (0.5, QuantileType(7)),
+            Statistic::Percentile(p, qtype) => (p / 100., qtype),
+        };
+        let get = |k: usize| if k < skip { sorted[k] } else { sorted[k + 1] };
+        quantile_by(sorted.len() - 1, get, p, qtype)
+    }
+
+    fn jackknife(&self, sorted: &[f64]) -> Vec<f64> {
+        let sum = sorted.iter().sum();
+        (0..sorted.len())
+            .map(|i| self.compute_without(sorted, i, sum))
+            .collect()
+    }
+
+    pub fn label(&self) -> String {
+        match *self {
+            Statistic::Mean => "mean".into(),
+            Statistic::Median => "median".into(),
+            Statistic::Percentile(p, _) => format!("p{}", p),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub estimate: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+/// Draws `resamples` bootstrap resamples of `sorted`, and evaluates each of
+/// `statistics` on them. The result is indexed by statistic, then resample.
+pub fn replicates(
+    sorted: &[f64],
+    statistics: &[Statistic],
+    resamples: usize,
+    rng: &mut Rng,
+) -> Vec<Vec<f64>> {
+    let n = sorted.len();
+    let mut rv = vec![Vec::with_capacity(resamples); statistics.len()];
+    let mut buf = Vec::with_capacity(n);
+    for _ in 0..resamples {
+        buf.clear();
+        buf.extend((0..n).map(|_| sorted[rng.below(n)]));
+        buf.sort_unstable_by(|a, b| a.total_cmp(b));
+        for (stat, reps) in statistics.iter().zip(rv.iter_mut()) {
+            reps.push(stat.compute(&buf));
+        }
+    }
+    rv
+}
+
+/// Acceleration constant for BCa intervals, from jackknife estimates. Each
+/// group holds the estimates from deleting one observation of one sample.
+/// With several samples, each one's influence values, (n_i - 1) times its
+/// jackknife deviations, are weighted by 1/n_i, as in Efron (1987).
+fn acceleration(groups: &[Vec<f64>]) -> f64 {
+    let mut num = 0.;
+    let mut den = 0.;
+    for group in groups {
+        let n = group.len() as f64;
+        let mean = group.iter().sum::<f64>() / n;
+        for v in group {
+            let d = (n - 1.) / n * (mean - v);
+            num += d.powi(3);
+            den += d.powi(2);
+        }
+    }
+    if den == 0. {
+        0.
+    } else {
+        num / (6. * den.powf(1.5))
+    }
+}
+
+/// Builds a confidence interval from bootstrap replicates. `jackknife` is only
+/// needed for BCa intervals.
+pub fn interval(
+    estimate: f64,
+    mut replicates: Vec<f64>,
+    jackknife: &[Vec<f64>],
+    level: f64,
+    method: BootstrapCi,
+) -> Interval {
+    replicates.sort_unstable_by(|a, b| a.total_cmp(b));
+    let alpha = (1. - level) / 2.;
+    let percentile = Interval {
+        estimate,
+        low: quantile(&replicates, alpha),
+        high: quantile(&replicates, 1. - alpha),
+    };
+    if method == BootstrapCi::Percentile {
+        return percentile;
+    }
+    let below = replicates.iter().filter(|&&r| r < estimate).count() as f64
+        + 0.5 * replicates.iter().filter(|&&r| r == estimate).count() as f64;
+    let proportion = below / replicates.len() as f64;
+    if proportion <= 0. || proportion >= 1. {
+        // The bias correction is infinite; there's nothing sensible to adjust
+        return percentile;
+    }
+    let z0 = normal_quantile(proportion);
+    let a = acceleration(jackknife);
+    let adjust = |z: f64| {
+        let zz = z0 + z;
+        normal_cdf(z0 + zz / (1. - a * zz))
+    };
+    let low = adjust(normal_quantile(alpha));
+    let high = adjust(normal_quantile(1. - alpha));
+    if !low.is_finite() || !high.is_finite() {
+        return percentile;
+    }
+    Interval {
+        estimate,
+        low: quantile(&replicates, low),
+        high: quantile(&replicates, high),
+    }
+}
+
+pub fn print_bootstrap<W, T>(
+    f: &mut W,
+    data: &[T],
+    baseline: usize,
+    resamples: usize,
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+    T: AsRef<[f64]>,
+{
+    // With --log the data are logs, but the intervals are for the original
+    // data's statistics
+    let raw: Option<Vec<Vec<f64>>> = opt.log.then(|| {
+        data.iter()
+            .map(|d| d.as_ref().iter().map(|x| x.exp()).collect())
+            .collect()
+    });
+    let data: Vec<&[f64]> = match &raw {
+        Some(raw) => raw.iter().map(|d| &d[..]).collect(),
+        None => data.iter().map(|d| d.as_ref()).collect(),
+    };
+    let level = opt.confidence_level.level();
+    let method = opt.bootstrap_ci;
+    let mut statistics = vec![Statistic::Mean, Statistic::Median];
+    statistics.extend(
+        opt.percentiles
+            .0
+            .iter()
+            .map(|&p| Statistic::Percentile(p, opt.quantile_type)),
+    );
+    let mut rng = Rng::new(opt.seed);
+
+    writeln!(
+        f,
+        "Bootstrap {}% confidence intervals ({} resamples, {})",
+        T_CONFIDENCES[opt.confidence_level.0],
+        resamples,
+        match method {
+            BootstrapCi::Bca => "BCa",
+            BootstrapCi::Percentile => "percentile",
+        }
+    )?;
+    writeln!(
+        f,
+        "  {:<12} {:>13} {:>13} {:>13}",
+        "Statistic", "Estimate", "Low", "High"
+    )?;
+    let write_row = |f: &mut W, symbol: char, label: &str, ci: Interval| {
+        writeln!(
+            f,
+            "{} {:<12} {:13.6} {:13.6} {:13.6}",
+            symbol, label, ci.estimate, ci.low, ci.high
+        )
+    };
+
+    // Resample every dataset up front, in order, so that the results don't
+    // depend on which dataset is the baseline
+    let all_reps: Vec<_> = data
+        .iter()
+        .map(|dataset| replicates(dataset, &statistics, resamples, &mut rng))
+        .collect();
+    let baseline_symbol = symbols[baseline + 1];
+    let baseline_reps = &all_reps[baseline];
+    let baseline_data = data[baseline];
+    for (idx, ((&symbol, dataset), reps)) in symbols
+        .iter()
+        .skip(1)
+        .zip(data.iter())
+        .zip(all_reps.iter())
+        .enumerate()
+    {
+        for (stat, stat_reps) in statistics.iter().zip(reps.iter()) {
+            let estimate = stat.compute(dataset);
+            let jack = stat.jackknife(dataset);
+            let ci = interval(
+                estimate,
+                stat_reps.clone(),
+                std::slice::from_ref(&jack),
+                level,
+                method,
+            );
+            write_row(f, symbol, &stat.label(), ci)?;
+            if idx == baseline {
+                continue;
+            }
+
+            // Pair each resample with the baseline's resample of the same index
+            let base_estimate = stat.compute(baseline_data);
+            let base_jack = stat.jackknife(baseline_data);
+            let base_reps = &baseline_reps[statistics.iter().position(|s| s == stat).unwrap()];
+
+            let diff_reps = stat_reps.iter().zip(base_reps).map(|(o, b)| o - b);
+            let diff_jack = [
+                base_jack.iter().map(|b| estimate - b).collect(),
+                jack.iter().map(|o| o - base_estimate).collect(),
+            ];
+            let ci = interval(
+                estimate - base_estimate,
+                diff_reps.collect(),
+                &diff_jack,
+                level,
+                method,
+            );
+            write_row(
+                f,
+                symbol,
+                &format!("{} - {}", stat.label(), baseline_symbol),
+                ci,
+            )?;
+
+            let ratio_reps = stat_reps.iter().zip(base_reps).map(|(o, b)| o / b);
+            let ratio_jack = [
+                base_jack.iter().map(|b| estimate / b).collect(),
+                jack.iter().map(|o| o / base_estimate).collect(),
+            ];
+            let ci = interval(
+                estimate / base_estimate,
+                ratio_reps.collect(),
+                &ratio_jack,
+                level,
+                method,
+            );
+            write_row(
+                f,
+                symbol,
+                &format!("{} / {}", stat.label(), baseline_symbol),
+                ci,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{acceleration, interval, replicates, Statistic};
+    use crate::{
+        args::{BootstrapCi, QuantileType},
+        rng::Rng,
+    };
+
+    #[test]
+    fn test_jackknife() {
+        let data = [1., 2., 4., 8., 16.];
+        for stat in [
+            Statistic::Mean,
+            Statistic::Median,
+            Statistic::Percentile(90., QuantileType(7)),
+            Statistic::Percentile(90., QuantileType(1)),
+            Statistic::Percentile(10., QuantileType(9)),
+        ] {
+            let jack = stat.jackknife(&data);
+            for (i, value) in jack.into_iter().enumerate() {
+                let mut without = data.to_vec();
+                without.remove(i);
+                assert_eq!(stat.compute(&without), value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_interval_covers_estimate() {
+        let data: Vec<f64> = (1..=50).map(|x| x as f64).collect();
+        let mut rng = Rng::new(0);
+        let reps = replicates(&data, &[Statistic::Mean], 2000, &mut rng);
+        let jack = Statistic::Mean.jackknife(&data);
+        for method in [BootstrapCi::Percentile, BootstrapCi::Bca] {
+            let ci = interval(
+                25.5,
+                reps[0].clone(),
+                std::slice::from_ref(&jack),
+                0.95,
+                method,
+            );
+            // The standard error of the mean here is about 2.04
+            assert!(ci.low < 25.5 && ci.high > 25.5);
+            assert!((ci.low - 21.5).abs() < 0.6, "{:?}", ci);
+            assert!((ci.high - 29.5).abs() < 0.6, "{:?}", ci);
+        }
+    }
+
+    #[test]
+    fn test_acceleration() {
+        // For a difference of means, the jackknife recovers the influence
+        // values exactly: each datapoint's deviation from its sample's mean,
+        // negated for the baseline
+        let base = [1., 2., 4., 8., 16.];
+        let other = [1., 3., 9., 27., 81., 243., 5., 7.];
+        let mean = |d: &[f64]| d.iter().sum::<f64>() / d.len() as f64;
+        let (base_mean, other_mean) = (mean(&base), mean(&other));
+        let groups = [
+            Statistic::Mean
+                .jackknife(&base)
+                .iter()
+                .map(|b| other_mean - b)
+                .collect(),
+            Statistic::Mean
+                .jackknife(&other)
+                .iter()
+                .map(|o| o - base_mean)
+                .collect(),
+        ];
+        let (mut num, mut den) = (0., 0.);
+        for (data, mean, sign) in [(&base[..], base_mean, -1.), (&other[..], other_mean, 1.)] {
+            let n = data.len() as f64;
+            for x in data {
+                let u = sign * (x - mean) / n;
+                num += u.powi(3);
+                den += u.powi(2);
+            }
+        }
+        let expected = num / (6. * den.powf(1.5));
+        assert!((acceleration(&groups) - expected).abs() < 1e-12);
+
+        // A single sample's is unaffected by the weighting
+        let jack = Statistic::Mean.jackknife(&other);
+        let skewness = other.iter().map(|x| (x - other_mean).powi(3)).sum::<f64>()
+            / other
+                .iter()
+                .map(|x| (x - other_mean).powi(2))
+                .sum::<f64>()
+                .powf(1.5);
+        assert!((acceleration(&[jack]) - skewness / 6.).abs() < 1e-12);
+    }
+}
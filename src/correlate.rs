@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Write};
+
+use anyhow::Error;
+
+use crate::anova::rank_all;
+use crate::args::{CorrelateOpt, Opt};
+use crate::data::read_pairs;
+use crate::dist::{normal_quantile, t_cdf};
+use crate::err::MinistatFailure;
+use crate::plot::plot_scatter;
+use crate::stats::t_critical;
+use crate::t_table::T_CONFIDENCES;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Correlation {
+    pub r: f64,
+    /// Two-sided p-value for a correlation of zero
+    pub p: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fit {
+    pub intercept: f64,
+    pub slope: f64,
+    /// Half-widths of the confidence intervals
+    pub intercept_error: f64,
+    pub slope_error: f64,
+    pub r_squared: f64,
+}
+
+/// Sums of squares and cross products about the means, and the means
+fn moments(x: &[f64], y: &[f64]) -> (f64, f64, f64, f64, f64) {
+    let n = x.len() as f64;
+    let x_mean = x.iter().sum::<f64>() / n;
+    let y_mean = y.iter().sum::<f64>() / n;
+    let (mut sxx, mut syy, mut sxy) = (0., 0., 0.);
+    for (x, y) in x.iter().zip(y) {
+        sxx += (x - x_mean).powi(2);
+        syy += (y - y_mean).powi(2);
+        sxy += (x - x_mean) * (y - y_mean);
+    }
+    (x_mean, y_mean, sxx, syy, sxy)
+}
+
+/// Correlation coefficient `r` of `n` pairs, with its p-value from Student's t
+/// and a confidence interval at `level` from Fisher's z-transformation, whose
+/// standard error is scaled by `se_factor`
+fn correlation(r: f64, n: f64, level: f64, se_factor: f64) -> Correlation {
+    let t = r * ((n - 2.) / (1. - r * r)).sqrt();
+    let half_width = normal_quantile(1. - (1. - level) / 2.) * se_factor / (n - 3.).sqrt();
+    let z = r.atanh();
+    Correlation {
+        r,
+        p: (2. * (1. - t_cdf(t.abs(), n - 2.))).min(1.),
+        low: (z - half_width).tanh(),
+        high: (z + half_width).tanh(),
+    }
+}
+
+/// Pearson's product-moment correlation
+pub fn pearson(x: &[f64], y: &[f64], level: f64) -> Correlation {
+    let (_, _, sxx, syy, sxy) = moments(x, y);
+    correlation(sxy / (sxx * syy).sqrt(), x.len() as f64, level, 1.)
+}
+
+/// Spearman's rank correlation, with Fieller et al.'s (1957) standard error
+/// for its confidence interval
+pub fn spearman(x: &[f64], y: &[f64], level: f64) -> Correlation {
+    let (x_ranks, _) = rank_all(&[x]);
+    let (y_ranks, _) = rank_all(&[y]);
+    let (_, _, sxx, syy, sxy) = moments(&x_ranks[0], &y_ranks[0]);
+    correlation(
+        sxy / (sxx * syy).sqrt(),
+        x.len() as f64,
+        level,
+        1.06f64.sqrt(),
+    )
+}
+
+/// Ordinary least-squares fit of y = intercept + slope * x, with confidence
+/// intervals from the t distribution with n - 2 degrees of freedom
+pub fn ols(x: &[f64], y: &[f64], confidence_idx: usize) -> Fit {
+    let n = x.len() as f64;
+    let (x_mean, y_mean, sxx, syy, sxy) = moments(x, y);
+    let slope = sxy / sxx;
+    let intercept = y_mean - slope * x_mean;
+    let residual_var = ((syy - slope * sxy) / (n - 2.)).max(0.);
+    let t = t_critical(n - 2., confidence_idx);
+    Fit {
+        intercept,
+        slope,
+        intercept_error: t * (residual_var * (1. / n + x_mean * x_mean / sxx)).sqrt(),
+        slope_error: t * (residual_var / sxx).sqrt(),
+        r_squared: sxy * sxy / (sxx * syy),
+    }
+}
+
+pub fn run_correlate(opt: &Opt, corr_opt: &CorrelateOpt) -> Result<(), Error> {
+    let mut stdout = std::io::stdout().lock();
+    let split_chars: HashSet<char> = opt.delimiter.chars().collect();
+    let (x_col, y_col) = (corr_opt.x.0, corr_opt.y.0);
+    let (name, pairs) = match &corr_opt.file {
+        Some(path) => (
+            path.to_string_lossy().into_owned(),
+            read_pairs(
+                BufReader::new(File::open(path)?),
+                path,
+                x_col,
+                y_col,
+                &split_chars,
+            )?,
+        ),
+        None => (
+            "stdin".to_string(),
+            read_pairs(
+                BufReader::new(std::io::stdin()),
+                "stdin",
+                x_col,
+                y_col,
+                &split_chars,
+            )?,
+        ),
+    };
+    if pairs.len() < 4 {
+        return Err(MinistatFailure::InsufficientPairs { file: name }.into());
+    }
+    let (x, y): (Vec<f64>, Vec<f64>) = pairs.iter().copied().unzip();
+    for (column, values) in [(x_col, &x), (y_col, &y)] {
+        if values.iter().all(|&v| v == values[0]) {
+            return Err(MinistatFailure::ConstantColumn { column }.into());
+        }
+    }
+
+    let symbols = crate::get_symbols(opt);
+    writeln!(
+        stdout,
+        "{} {}: column {} (y) against column {} (x)",
+        symbols[1], name, y_col, x_col
+    )?;
+    let level = opt.confidence_level.level();
+    let fit = ols(&x, &y, opt.confidence_level.0);
+    if !opt.stats_only {
+        plot_scatter(
+            &mut stdout,
+            crate::get_width(opt),
+            corr_opt.height,
+            &pairs,
+            Some((fit.intercept, fit.slope)),
+            symbols[1],
+            opt,
+        )?;
+    }
+
+    writeln!(
+        stdout,
+        "N = {}, at {}% confidence",
+        pairs.len(),
+        T_CONFIDENCES[opt.confidence_level.0]
+    )?;
+    for (name, result) in [
+        ("Pearson r", pearson(&x, &y, level)),
+        ("Spearman rho", spearman(&x, &y, level)),
+    ] {
+        writeln!(
+            stdout,
+            "{:<13} {:.6} [{:.6}, {:.6}], p = {:.6}",
+            name, result.r, result.low, result.high, result.p
+        )?;
+    }
+    writeln!(
+        stdout,
+        "Least squares y = intercept + slope * x, R^2 = {:.6}",
+        fit.r_squared
+    )?;
+    for (name, estimate, error) in [
+        ("Slope", fit.slope, fit.slope_error),
+        ("Intercept", fit.intercept, fit.intercept_error),
+    ] {
+        writeln!(
+            stdout,
+            "\t{:<10} {:.6} [{:.6}, {:.6}]",
+            name,
+            estimate,
+            estimate - error,
+            estimate + error
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ols, pearson, spearman};
+
+    #[test]
+    fn test_correlate() {
+        let x = [1., 2., 3., 4., 5., 6., 7., 8.];
+        let y = [2.1, 3.9, 6.2, 7.8, 10.1, 12.2, 13.8, 16.1];
+        // cor.test(x, y)
+        let result = pearson(&x, &y, 0.95);
+        assert!((result.r - 0.9994195).abs() < 1e-7);
+        assert!(result.p < 1e-8);
+        assert!((result.low - 0.9966538).abs() < 1e-6);
+        assert!((result.high - 0.9998994).abs() < 1e-6);
+
+        // Any increasing relationship has a rank correlation of 1, while
+        // shuffling the ranks weakens it
+        let squares: Vec<f64> = x.iter().map(|x| x * x).collect();
+        assert!((spearman(&x, &squares, 0.95).r - 1.).abs() < 1e-12);
+        let shuffled = [2., 1., 4., 3., 6., 5., 8., 7.];
+        // 1 - 6 * sum(d^2) / (n^3 - n) with every d = 1
+        let result = spearman(&x, &shuffled, 0.95);
+        assert!((result.r - (1. - 6. * 8. / 504.)).abs() < 1e-12);
+
+        // y = 2 x fits exactly, and alternating noise loosens the fit
+        let y = [2., 4., 6., 8., 10., 12., 14., 16.];
+        let noisy: Vec<f64> = y
+            .iter()
+            .enumerate()
+            .map(|(i, y)| y + if i % 2 == 0 { 0.5 } else { -0.5 })
+            .collect();
+        let fit = ols(&x, &y, 2);
+        assert!((fit.slope - 2.).abs() < 1e-12 && fit.intercept.abs() < 1e-12);
+        assert!(fit.slope_error.abs() < 1e-12);
+        assert!((fit.r_squared - 1.).abs() < 1e-12);
+        let fit = ols(&x, &noisy, 2);
+        assert!(fit.slope_error > 0. && fit.intercept_error > fit.slope_error);
+        assert!(fit.r_squared < 1.);
+    }
+}
@@ -9,10 +9,14 @@ use noisy_float::prelude::*;
 
 use crate::args::Opt;
 use crate::err::MinistatFailure;
+use crate::parallel::{jobs, parallel_map};
 
 pub struct Dataset {
     pub path: PathBuf,
+    /// The datapoints, sorted
     pub data: Vec<f64>,
+    /// The same datapoints in the order they were read
+    pub sequence: Vec<f64>,
 }
 
 impl Dataset {
@@ -40,39 +44,100 @@ impl Dataset {
                 }
             }
         }
+        let sequence = rv.iter().map(|x| x.raw()).collect();
         rv.sort();
 
         Ok(Dataset {
             path: name.as_ref().into(),
             data: rv.into_iter().map(|x| x.raw()).collect(),
+            sequence,
         })
     }
+
+    /// Keeps only the datapoints for which `f` is true
+    pub fn retain<F: Fn(f64) -> bool>(&mut self, f: F) {
+        self.data.retain(|&x| f(x));
+        self.sequence.retain(|&x| f(x));
+    }
+
+    /// Replaces every datapoint with its natural logarithm, which keeps the
+    /// data sorted. Every datapoint must be positive.
+    pub fn log_transform(&mut self) -> Result<(), Error> {
+        if let Some(&value) = self.data.iter().find(|&&x| x <= 0.) {
+            return Err(MinistatFailure::NonPositiveLog {
+                file: self.path.to_string_lossy().into_owned(),
+                value,
+            }
+            .into());
+        }
+        for x in self.data.iter_mut().chain(&mut self.sequence) {
+            *x = x.ln();
+        }
+        Ok(())
+    }
 }
 
+/// Reads the pairs of values in columns `x_col` and `y_col` of each line, in
+/// their original order, skipping lines without both
+pub fn read_pairs<R: Read, P: AsRef<Path>>(
+    r: BufReader<R>,
+    name: P,
+    x_col: u8,
+    y_col: u8,
+    split_chars: &HashSet<char>,
+) -> Result<Vec<(f64, f64)>, Error> {
+    let mut rv = Vec::new();
+    for (i, line) in r.lines().enumerate() {
+        let line = line?;
+        let fields: Vec<&str> = line.split(|x| split_chars.contains(&x)).collect();
+        let (x, y) = match (
+            fields.get((x_col - 1) as usize),
+            fields.get((y_col - 1) as usize),
+        ) {
+            (Some(x), Some(y)) => (x, y),
+            _ => continue,
+        };
+        let parse = |val: &str| {
+            val.parse::<f64>()
+                .map_err(|_| MinistatFailure::InvalidData {
+                    file: name.as_ref().to_string_lossy().into_owned(),
+                    line_no: i + 1,
+                })
+        };
+        let (x, y) = (parse(x)?, parse(y)?);
+        if x.is_finite() && y.is_finite() {
+            rv.push((x, y));
+        }
+    }
+    Ok(rv)
+}
+
+impl AsRef<Path> for Dataset {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Reads every dataset, loading files on up to --jobs threads. If any fail,
+/// the error is the first file's in command-line order.
 pub fn load_data(opt: &Opt) -> Result<Vec<Dataset>, Error> {
     use std::io;
-    let mut datas: Vec<Dataset> = Vec::new();
     let split_chars: HashSet<char> = opt.delimiter.chars().collect();
     if opt.files.is_empty() {
         let reader = BufReader::new(io::stdin());
         let name = "stdin";
-        datas.push(Dataset::from_reader(
+        return Ok(vec![Dataset::from_reader(
             reader,
             name,
             opt.column.0,
             &split_chars,
-        )?);
-    } else {
-        for fname in &opt.files {
-            let f = File::open(fname)?;
-            let reader = BufReader::new(f);
-            datas.push(Dataset::from_reader(
-                reader,
-                fname,
-                opt.column.0,
-                &split_chars,
-            )?);
-        }
+        )?]);
     }
-    Ok(datas)
+    parallel_map(&opt.files, jobs(opt), |fname| {
+        let f = File::open(fname)?;
+        let reader = BufReader::new(f);
+        Dataset::from_reader(reader, fname, opt.column.0, &split_chars)
+    })
+    .into_iter()
+    .collect()
 }
@@ -0,0 +1,439 @@
+//! Distribution functions needed for p-values and for critical values that
+//! don't come out of `T_TABLE`.
+
+use std::f64::consts::{PI, SQRT_2};
+
+/// Natural log of the gamma function, using the Lanczos approximation
+pub fn ln_gamma(x: f64) -> f64 {
+    const G: f64 = 7.;
+    const COEF: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        return (PI / (PI * x).sin()).ln() - ln_gamma(1. - x);
+    }
+    let x = x - 1.;
+    let mut a = COEF[0];
+    let t = x + G + 0.5;
+    for (i, c) in COEF.iter().enumerate().skip(1) {
+        a += c / (x + i as f64);
+    }
+    0.5 * (2. * PI).ln() + (x + 0.5) * t.ln() - t + a.ln()
+}
+
+/// Regularized lower incomplete gamma function P(a, x)
+pub fn gamma_p(a: f64, x: f64) -> f64 {
+    if x <= 0. {
+        0.
+    } else if x < a + 1. {
+        gamma_series(a, x)
+    } else {
+        1. - gamma_cf(a, x)
+    }
+}
+
+/// Regularized upper incomplete gamma function Q(a, x) = 1 - P(a, x)
+pub fn gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0. {
+        1.
+    } else if x < a + 1. {
+        1. - gamma_series(a, x)
+    } else {
+        gamma_cf(a, x)
+    }
+}
+
+fn gamma_series(a: f64, x: f64) -> f64 {
+    let mut ap = a;
+    let mut sum = 1. / a;
+    let mut del = sum;
+    for _ in 0..1000 {
+        ap += 1.;
+        del *= x / ap;
+        sum += del;
+        if del.abs() < sum.abs() * 1e-15 {
+            break;
+        }
+    }
+    sum * (-x + a * x.ln() - ln_gamma(a)).exp()
+}
+
+fn gamma_cf(a: f64, x: f64) -> f64 {
+    // Modified Lentz's method
+    let tiny = 1e-300;
+    let mut b = x + 1. - a;
+    let mut c = 1. / tiny;
+    let mut d = 1. / b;
+    let mut h = d;
+    for i in 1..1000 {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.;
+        d = an * d + b;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = b + an / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1. / d;
+        let del = d * c;
+        h *= del;
+        if (del - 1.).abs() < 1e-15 {
+            break;
+        }
+    }
+    (-x + a * x.ln() - ln_gamma(a)).exp() * h
+}
+
+/// Complementary error function
+pub fn erfc(x: f64) -> f64 {
+    if x >= 0. {
+        gamma_q(0.5, x * x)
+    } else {
+        1. + gamma_p(0.5, x * x)
+    }
+}
+
+/// Cumulative distribution function of the standard normal distribution
+pub fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / SQRT_2)
+}
+
+/// Inverse of `normal_cdf`, using Acklam's rational approximation followed
+/// by a Halley refinement step
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    if p <= 0. {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1. {
+        return f64::INFINITY;
+    }
+    let p_low = 0.02425;
+    let x = if p < p_low {
+        let q = (-2. * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.)
+    } else if p <= 1. - p_low {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.)
+    } else {
+        let q = (-2. * (1. - p).ln()).sqrt();
+        -(((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.)
+    };
+    let e = normal_cdf(x) - p;
+    let u = e * (2. * PI).sqrt() * (x * x / 2.).exp();
+    x - u / (1. + x * u / 2.)
+}
+
+/// Regularized incomplete beta function I_x(a, b)
+pub fn beta_i(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    if x >= 1. {
+        return 1.;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1. - x).ln()).exp();
+    // The continued fraction converges quickly only on this side of the mean
+    if x < (a + 1.) / (a + b + 2.) {
+        front * beta_cf(a, b, x) / a
+    } else {
+        1. - front * beta_cf(b, a, 1. - x) / b
+    }
+}
+
+fn beta_cf(a: f64, b: f64, x: f64) -> f64 {
+    // Modified Lentz's method
+    let tiny = 1e-300;
+    let qab = a + b;
+    let qap = a + 1.;
+    let qam = a - 1.;
+    let mut c = 1.;
+    let mut d = 1. - qab * x / qap;
+    if d.abs() < tiny {
+        d = tiny;
+    }
+    d = 1. / d;
+    let mut h = d;
+    for m in 1..1000 {
+        let m = m as f64;
+        let m2 = 2. * m;
+        let aa = m * (b - m) * x / ((qam + m2) * (a + m2));
+        d = 1. + aa * d;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = 1. + aa / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1. / d;
+        h *= d * c;
+        let aa = -(a + m) * (qab + m) * x / ((a + m2) * (qap + m2));
+        d = 1. + aa * d;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = 1. + aa / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1. / d;
+        let del = d * c;
+        h *= del;
+        if (del - 1.).abs() < 1e-15 {
+            break;
+        }
+    }
+    h
+}
+
+/// Cumulative distribution function of Student's t distribution with `df`
+/// degrees of freedom (which needn't be an integer)
+pub fn t_cdf(t: f64, df: f64) -> f64 {
+    let tail = 0.5 * beta_i(df / 2., 0.5, df / (df + t * t));
+    if t > 0. {
+        1. - tail
+    } else {
+        tail
+    }
+}
+
+/// Inverse of `t_cdf`
+pub fn t_quantile(p: f64, df: f64) -> f64 {
+    invert(|t| t_cdf(t, df), p, normal_quantile(p))
+}
+
+/// Probability of at most `k` successes in `n` trials with success
+/// probability `p`
+pub fn binomial_cdf(k: f64, n: f64, p: f64) -> f64 {
+    if k < 0. {
+        return 0.;
+    }
+    if k >= n {
+        return 1.;
+    }
+    beta_i(n - k, k + 1., 1. - p)
+}
+
+/// Cumulative distribution function of the F distribution
+pub fn f_cdf(x: f64, d1: f64, d2: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    beta_i(d1 / 2., d2 / 2., d1 * x / (d1 * x + d2))
+}
+
+/// Cumulative distribution function of the chi-squared distribution
+pub fn chi2_cdf(x: f64, k: f64) -> f64 {
+    gamma_p(k / 2., x / 2.)
+}
+
+/// Cumulative distribution function of the studentized range of `k` means
+/// with `df` degrees of freedom, by numerical integration over the
+/// distribution of the sample standard deviation
+pub fn ptukey(q: f64, k: f64, df: f64) -> f64 {
+    if q <= 0. {
+        return 0.;
+    }
+    // Distribution of the range of k standard normals. Phi(z) only depends
+    // on the grid, so it's computed once
+    let (z_lo, z_hi, z_steps) = (-8., 8., 160);
+    let dz = (z_hi - z_lo) / z_steps as f64;
+    let grid: Vec<(f64, f64, f64)> = (0..=z_steps)
+        .map(|i| {
+            let z = z_lo + i as f64 * dz;
+            let density = (-0.5 * z * z).exp() / (2. * PI).sqrt();
+            (z, density, normal_cdf(z))
+        })
+        .collect();
+    let range_cdf = |w: f64| {
+        let values: Vec<f64> = grid
+            .iter()
+            .map(|&(z, density, cdf)| density * (cdf - normal_cdf(z - w)).max(0.).powf(k - 1.))
+            .collect();
+        (k * simpson(&values, dz)).min(1.)
+    };
+    if df > 5000. {
+        return range_cdf(q);
+    }
+
+    // s = sqrt(chi2(df) / df) is concentrated near 1
+    let spread = 12. / (2. * df).sqrt();
+    let (s_lo, s_hi, s_steps) = ((1. - spread).max(0.), 1. + spread, 200);
+    let ds = (s_hi - s_lo) / s_steps as f64;
+    let ln_norm = df / 2. * df.ln() - ln_gamma(df / 2.) - (df / 2. - 1.) * 2f64.ln();
+    let values: Vec<f64> = (0..=s_steps)
+        .map(|i| {
+            let s = s_lo + i as f64 * ds;
+            if s <= 0. {
+                return 0.;
+            }
+            let density = (ln_norm + (df - 1.) * s.ln() - df * s * s / 2.).exp();
+            density * range_cdf(q * s)
+        })
+        .collect();
+    simpson(&values, ds).clamp(0., 1.)
+}
+
+/// Composite Simpson's rule over equally spaced samples. There must be an odd
+/// number of them.
+fn simpson(values: &[f64], h: f64) -> f64 {
+    let last = values.len() - 1;
+    let sum: f64 = values
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            if i == 0 || i == last {
+                v
+            } else if i % 2 == 1 {
+                4. * v
+            } else {
+                2. * v
+            }
+        })
+        .sum();
+    sum * h / 3.
+}
+
+/// Finds x such that `cdf(x) = p` for an increasing `cdf`, starting from a
+/// guess. The bracket is widened until it contains the answer, then bisected.
+fn invert<F>(cdf: F, p: f64, guess: f64) -> f64
+where
+    F: Fn(f64) -> f64,
+{
+    if p <= 0. {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1. {
+        return f64::INFINITY;
+    }
+    let mut step = guess.abs().max(1.);
+    let mut lo = guess - step;
+    while cdf(lo) > p {
+        step *= 2.;
+        lo = guess - step;
+    }
+    let mut step = guess.abs().max(1.);
+    let mut hi = guess + step;
+    while cdf(hi) < p {
+        step *= 2.;
+        hi = guess + step;
+    }
+    for _ in 0..200 {
+        let mid = 0.5 * (lo + hi);
+        if mid <= lo || mid >= hi {
+            break;
+        }
+        if cdf(mid) < p {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    0.5 * (lo + hi)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: f64, b: f64, tol: f64) -> bool {
+        (a - b).abs() <= tol
+    }
+
+    #[test]
+    fn test_ln_gamma() {
+        assert!(close(ln_gamma(1.), 0., 1e-12));
+        assert!(close(ln_gamma(5.), 24f64.ln(), 1e-12));
+        assert!(close(ln_gamma(0.5), PI.sqrt().ln(), 1e-12));
+    }
+
+    #[test]
+    fn test_normal() {
+        assert!(close(normal_cdf(0.), 0.5, 1e-15));
+        assert!(close(normal_cdf(1.959963984540054), 0.975, 1e-12));
+        assert!(close(normal_cdf(-3.), 0.0013498980316301, 1e-14));
+        assert!(close(normal_quantile(0.975), 1.959963984540054, 1e-9));
+        assert!(close(normal_quantile(0.001), -3.090232306167813, 1e-9));
+    }
+
+    #[test]
+    fn test_t() {
+        use crate::t_table::T_TABLE;
+        assert!(close(t_cdf(0., 5.), 0.5, 1e-15));
+        // Check against the table used for the Welch test
+        for (dof, row) in [(1, T_TABLE[0]), (10, T_TABLE[9]), (100, T_TABLE[99])] {
+            assert!(close(t_cdf(row[2], dof as f64), 0.975, 1e-6));
+            assert!(close(t_quantile(0.975, dof as f64), row[2], 1e-5));
+            assert!(close(t_quantile(0.995, dof as f64), row[4], 1e-5));
+        }
+    }
+
+    #[test]
+    fn test_f_chi2() {
+        // qf(0.95, 2, 10) and qchisq(0.95, 3)
+        assert!(close(f_cdf(4.102821, 2., 10.), 0.95, 1e-6));
+        assert!(close(chi2_cdf(7.814728, 3.), 0.95, 1e-6));
+    }
+
+    #[test]
+    fn test_binomial() {
+        assert!(close(binomial_cdf(0., 5., 0.5), 1. / 32., 1e-12));
+        assert!(close(binomial_cdf(2., 5., 0.5), 0.5, 1e-12));
+        // pbinom(3, 10, 0.2)
+        assert!(close(binomial_cdf(3., 10., 0.2), 0.8791261, 1e-7));
+        assert_eq!(1., binomial_cdf(5., 5., 0.5));
+    }
+
+    #[test]
+    fn test_ptukey() {
+        // qtukey(0.95, 3, 20) and qtukey(0.95, 2, Inf)
+        assert!(close(ptukey(3.577935, 3., 20.), 0.95, 1e-4));
+        assert!(close(ptukey(2.771808, 2., 1e6), 0.95, 1e-4));
+        assert!(close(ptukey(2.771808, 2., f64::INFINITY), 0.95, 1e-4));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::bootstrap::Interval;
+use crate::dist::normal_quantile;
+use crate::stats::Stats;
+
+/// Standardised difference in means, other minus baseline, using the pooled
+/// standard deviation
+pub fn cohens_d(baseline: &Stats, other: &Stats) -> f64 {
+    let (n1, n2) = (baseline.n as f64, other.n as f64);
+    let pooled_var = ((n1 - 1.) * baseline.var + (n2 - 1.) * other.var) / (n1 + n2 - 2.);
+    (other.mean - baseline.mean) / pooled_var.sqrt()
+}
+
+/// Cohen's d with Hedges' small-sample bias correction, and its confidence
+/// interval at `level` using the usual normal approximation to its
+/// standard error
+pub fn hedges_g(baseline: &Stats, other: &Stats, level: f64) -> Interval {
+    let (n1, n2) = (baseline.n as f64, other.n as f64);
+    let correction = 1. - 3. / (4. * (n1 + n2) - 9.);
+    let g = cohens_d(baseline, other) * correction;
+    let se = ((n1 + n2) / (n1 * n2) + g * g / (2. * (n1 + n2))).sqrt();
+    let z = normal_quantile(1. - (1. - level) / 2.);
+    Interval {
+        estimate: g,
+        low: g - z * se,
+        high: g + z * se,
+    }
+}
+
+/// Cliff's delta: the probability that a value from `other` is larger than
+/// one from `baseline`, minus the probability that it is smaller. Both
+/// datasets must be sorted.
+pub fn cliffs_delta(baseline: &[f64], other: &[f64]) -> f64 {
+    let mut dominance = 0i64;
+    for &x in other {
+        let below = baseline.partition_point(|&b| b < x);
+        let not_above = baseline.partition_point(|&b| b <= x);
+        dominance += below as i64 - (baseline.len() - not_above) as i64;
+    }
+    dominance as f64 / (baseline.len() * other.len()) as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::{cliffs_delta, cohens_d, hedges_g};
+    use crate::stats::Stats;
+
+    #[test]
+    fn test_effect_sizes() {
+        let a = Stats::from_dataset(&[1., 2., 3., 4., 5.]);
+        let b = Stats::from_dataset(&[3., 4., 5., 6., 7.]);
+        // Both have a variance of 2.5
+        assert!((cohens_d(&a, &b) - 2. / 2.5f64.sqrt()).abs() < 1e-12);
+        let g = hedges_g(&a, &b, 0.95);
+        assert!((g.estimate - 2. / 2.5f64.sqrt() * (1. - 3. / 31.)).abs() < 1e-12);
+        assert!(g.low < g.estimate && g.estimate < g.high);
+
+        assert_eq!(1., cliffs_delta(&[1., 2.], &[3., 4.]));
+        assert_eq!(-1., cliffs_delta(&[3., 4.], &[1., 2.]));
+        assert_eq!(0., cliffs_delta(&[1., 2., 3.], &[1., 2., 3.]));
+        // 3 beats 1 and 2, and ties with 3
+        assert!((cliffs_delta(&[1., 2., 3.], &[3.]) - 2. / 3.).abs() < 1e-12);
+    }
+}
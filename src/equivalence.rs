@@ -0,0 +1,160 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::{Margin, Opt};
+use crate::dist::{t_cdf, t_quantile};
+use crate::stats::{others, welch, Stats};
+use crate::t_table::T_CONFIDENCES;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Equivalent,
+    NotEquivalent,
+    Inconclusive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tost {
+    pub verdict: Verdict,
+    /// Difference in means, other minus baseline
+    pub difference: f64,
+    /// Absolute equivalence margin
+    pub margin: f64,
+    /// Ends of the (1 - 2 alpha) confidence interval for the difference
+    pub low: f64,
+    pub high: f64,
+    /// The larger of the two one-sided p-values
+    pub p: f64,
+}
+
+/// Two one-sided Welch tests of whether `other`'s mean is within `margin` of
+/// the baseline's, at significance `alpha`. The difference is equivalent when
+/// the (1 - 2 alpha) confidence interval lies inside the margin, and not
+/// equivalent when it lies entirely outside it.
+pub fn tost(baseline: &Stats, other: &Stats, margin: Margin, alpha: f64) -> Tost {
+    let margin = match margin {
+        Margin::Absolute(m) => m,
+        Margin::Relative(percent) => (percent / 100. * baseline.mean).abs(),
+    };
+    let (val, dof) = welch(other, baseline);
+    let se = val.sqrt();
+    let difference = other.mean - baseline.mean;
+    let p_lower = 1. - t_cdf((difference + margin) / se, dof);
+    let p_upper = t_cdf((difference - margin) / se, dof);
+    let half_width = t_quantile(1. - alpha, dof) * se;
+    let (low, high) = (difference - half_width, difference + half_width);
+    let verdict = if -margin < low && high < margin {
+        Verdict::Equivalent
+    } else if low > margin || high < -margin {
+        Verdict::NotEquivalent
+    } else {
+        Verdict::Inconclusive
+    };
+    Tost {
+        verdict,
+        difference,
+        margin,
+        low,
+        high,
+        p: p_lower.max(p_upper),
+    }
+}
+
+pub fn print_equivalence<W>(
+    f: &mut W,
+    stats: &[Stats],
+    baseline: usize,
+    margin: Margin,
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+{
+    let confidence_label = T_CONFIDENCES[opt.confidence_level.0];
+    let alpha = 1. - opt.confidence_level.level();
+    let margin_label = match margin {
+        Margin::Absolute(m) => format!("{}", m),
+        Margin::Relative(percent) => format!("{}%", percent),
+    };
+    // With --log, differences in the means of logs are ratios of geometric
+    // means, so a margin of p% is one of ln(1 + p%) between the logs
+    let margin = match margin {
+        Margin::Relative(percent) if opt.log => Margin::Absolute((percent / 100.).ln_1p()),
+        margin => margin,
+    };
+    let others = others(symbols, stats, baseline);
+    let baseline = &stats[baseline];
+    for (&symbol, other) in others {
+        let result = tost(baseline, other, margin, alpha);
+        match result.verdict {
+            Verdict::Equivalent => writeln!(
+                f,
+                "{} Equivalent within +/-{} at {}% confidence",
+                symbol, margin_label, confidence_label
+            )?,
+            Verdict::NotEquivalent => writeln!(
+                f,
+                "{} Not equivalent within +/-{} at {}% confidence",
+                symbol, margin_label, confidence_label
+            )?,
+            Verdict::Inconclusive => writeln!(
+                f,
+                "{} Inconclusive whether equivalent within +/-{} at {}% confidence",
+                symbol, margin_label, confidence_label
+            )?,
+        }
+        if opt.log {
+            writeln!(
+                f,
+                "\t{:.6}x in [{:.6}, {:.6}], margin {:.6}x to {:.6}x",
+                result.difference.exp(),
+                result.low.exp(),
+                result.high.exp(),
+                (-result.margin).exp(),
+                result.margin.exp()
+            )?;
+        } else {
+            writeln!(
+                f,
+                "\t{:.6} in [{:.6}, {:.6}], margin +/-{:.6}",
+                result.difference, result.low, result.high, result.margin
+            )?;
+        }
+        writeln!(f, "\t(TOST p = {:.6})", result.p)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tost, Verdict};
+    use crate::{args::Margin, stats::Stats};
+
+    #[test]
+    fn test_tost() {
+        let a: Vec<f64> = (0..100).map(|x| 100. + (x / 10) as f64).collect();
+        let b: Vec<f64> = (0..100).map(|x| 100.5 + (x / 10) as f64).collect();
+        let c: Vec<f64> = (0..100).map(|x| 110. + (x / 10) as f64).collect();
+        let (a, b, c) = (
+            Stats::from_dataset(&a),
+            Stats::from_dataset(&b),
+            Stats::from_dataset(&c),
+        );
+
+        let result = tost(&a, &b, Margin::Relative(2.), 0.05);
+        assert_eq!(Verdict::Equivalent, result.verdict);
+        assert!((result.margin - 2.09).abs() < 1e-12);
+        assert!(result.p < 0.05);
+
+        assert_eq!(
+            Verdict::NotEquivalent,
+            tost(&a, &c, Margin::Absolute(2.), 0.05).verdict
+        );
+        assert_eq!(
+            Verdict::Inconclusive,
+            tost(&a, &b, Margin::Absolute(0.6), 0.05).verdict
+        );
+    }
+}
@@ -11,10 +11,127 @@ pub enum MinistatFailure {
         provided_confidence
     )]
     InvalidConfidence { provided_confidence: String },
+    #[error(
+        "'{}' is not a valid test (must be one of welch, ks and permutation)",
+        provided_test
+    )]
+    InvalidTest { provided_test: String },
+    #[error(
+        "'{}' is not a valid bootstrap interval method (must be one of bca and percentile)",
+        provided_method
+    )]
+    InvalidBootstrapCi { provided_method: String },
+    #[error(
+        "'{}' is not a valid ratio interval method (must be one of fieller and delta)",
+        provided_method
+    )]
+    InvalidRatioCi { provided_method: String },
+    #[error(
+        "'{}' is not a valid bandwidth (must be one of silverman and scott)",
+        provided_bandwidth
+    )]
+    InvalidBandwidth { provided_bandwidth: String },
+    #[error(
+        "'{}' is not a valid one-sample test (must be one of t, sign and wilcoxon)",
+        provided_test
+    )]
+    InvalidAgainstTest { provided_test: String },
+    #[error(
+        "'{}' is not a valid percentile (must be between 0 and 100)",
+        provided_percentile
+    )]
+    InvalidPercentile { provided_percentile: String },
+    #[error(
+        "'{}' is not a valid quantile type (must be from 1 to 9)",
+        provided_type
+    )]
+    InvalidQuantileType { provided_type: String },
+    #[error(
+        "'{}' is not a valid spread (must be one of mad and iqr)",
+        provided_spread
+    )]
+    InvalidSpread { provided_spread: String },
+    #[error(
+        "'{}' is not a valid trim (must be at least 0 and less than 0.5)",
+        provided_trim
+    )]
+    InvalidTrim { provided_trim: String },
+    #[error(
+        "'{}' is not a valid power (must be a percentage greater than 0 and less than 100)",
+        provided_power
+    )]
+    InvalidPower { provided_power: String },
+    #[error(
+        "'{}' is not a valid outlier method (must be one of tukey, mad and grubbs)",
+        provided_method
+    )]
+    InvalidOutlierMethod { provided_method: String },
+    #[error(
+        "'{}' is not a valid outlier multiplier (must be greater than 0)",
+        provided_k
+    )]
+    InvalidOutlierK { provided_k: String },
+    #[error(
+        "'{}' is not a valid margin (must be a positive number, optionally followed by %)",
+        provided_margin
+    )]
+    InvalidMargin { provided_margin: String },
+    #[error(
+        "'{}' is not a valid correction (must be one of bonferroni, holm and bh)",
+        provided_correction
+    )]
+    InvalidCorrection { provided_correction: String },
+    #[error(
+        "'{}' is not a valid baseline (must be a dataset's number, starting from 1, or its file name)",
+        provided_baseline
+    )]
+    InvalidBaseline { provided_baseline: String },
+    #[error(
+        "'{}' is not a valid statistic (must be one of mean and median)",
+        provided_location
+    )]
+    InvalidLocation { provided_location: String },
     #[error("Dataset {} must contain at least 3 datapoints. (Perhaps there was not enough data in the column you selected?)", file)]
     InsufficientData { file: String },
+    #[error(
+        "{} must contain at least 4 lines with both columns to correlate them",
+        file
+    )]
+    InsufficientPairs { file: String },
+    #[error(
+        "Column {} holds the same value throughout, so it can't be correlated",
+        column
+    )]
+    ConstantColumn { column: u8 },
+    #[error(
+        "Dataset {} contains {}, but --log needs every datapoint to be positive",
+        file,
+        value
+    )]
+    NonPositiveLog { file: String, value: f64 },
+    #[error(
+        "--against {} can't be compared on a log scale; it must be positive",
+        value
+    )]
+    NonPositiveAgainst { value: f64 },
+    #[error(
+        "--log compares datasets by ratios, so --{} {} must be a percentage",
+        option,
+        margin
+    )]
+    AbsoluteLogMargin { option: &'static str, margin: f64 },
     #[error("Unable to create a plot for this data")]
     NoPlotPossible,
+    #[error(
+        "Sequential mode compares exactly two files, or reads both datasets from standard input; you had {}",
+        file_count
+    )]
+    SequentialFiles { file_count: usize },
+    #[error(
+        "--{} needs every datapoint, so it can't be used with --streaming",
+        option
+    )]
+    StreamingUnsupported { option: &'static str },
     #[error("Too many datasets. You may have at most 7; you had {}", dataset_count)]
     TooManyDatasets { dataset_count: usize },
 }
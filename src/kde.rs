@@ -0,0 +1,207 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::{Bandwidth, Opt};
+use crate::stats::Stats;
+
+/// Number of points the density is evaluated at
+const GRID_POINTS: usize = 512;
+
+/// Peaks that rise less than this fraction of the highest one's height above
+/// the valleys beside them are treated as noise
+const MIN_PROMINENCE: f64 = 0.05;
+
+/// Above this bimodality coefficient, the value for a uniform distribution,
+/// the data may be bimodal
+const BIMODALITY_THRESHOLD: f64 = 5. / 9.;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kde {
+    pub bandwidth: f64,
+    /// Points at which the density was evaluated, and its values there
+    pub density: Vec<(f64, f64)>,
+}
+
+/// The rule-of-thumb bandwidth for a Gaussian KDE of data with these stats,
+/// Silverman's 0.9 min(sd, IQR / 1.34) n^(-1/5) or Scott's 1.06 sd n^(-1/5)
+pub fn bandwidth(stats: &Stats, method: Bandwidth) -> f64 {
+    let scale = stats.n as f64;
+    match method {
+        Bandwidth::Silverman => {
+            let spread = match stats.stddev.min(stats.iqr / 1.34) {
+                s if s > 0. => s,
+                _ => stats.stddev,
+            };
+            0.9 * spread * scale.powf(-0.2)
+        }
+        Bandwidth::Scott => 1.06 * stats.stddev * scale.powf(-0.2),
+    }
+}
+
+/// Gaussian kernel density estimate of sorted data, evaluated on an even grid
+/// reaching three bandwidths beyond the data. Returns `None` if the data has
+/// no spread to set a bandwidth with.
+pub fn kde(sorted: &[f64], stats: &Stats, method: Bandwidth) -> Option<Kde> {
+    let h = bandwidth(stats, method);
+    if h <= 0. {
+        return None;
+    }
+    let (low, high) = (stats.min - 3. * h, stats.max + 3. * h);
+    let step = (high - low) / (GRID_POINTS - 1) as f64;
+    let norm = 1. / (sorted.len() as f64 * h * (2. * std::f64::consts::PI).sqrt());
+    let density = (0..GRID_POINTS)
+        .map(|i| {
+            let x = low + i as f64 * step;
+            // Points more than 8 bandwidths away contribute nothing visible
+            let start = sorted.partition_point(|&v| v < x - 8. * h);
+            let end = sorted.partition_point(|&v| v <= x + 8. * h);
+            let sum: f64 = sorted[start..end]
+                .iter()
+                .map(|v| (-0.5 * ((x - v) / h).powi(2)).exp())
+                .sum();
+            (x, sum * norm)
+        })
+        .collect();
+    Some(Kde {
+        bandwidth: h,
+        density,
+    })
+}
+
+impl Kde {
+    /// Locations and densities of the peaks of the density, ignoring those
+    /// less prominent than `MIN_PROMINENCE`
+    pub fn modes(&self) -> Vec<(f64, f64)> {
+        let density: Vec<f64> = self.density.iter().map(|p| p.1).collect();
+        let highest = density.iter().copied().fold(0., f64::max);
+        // The lowest the density falls on one side of a peak before rising
+        // above it again
+        let valley = |peak: f64, side: &mut dyn Iterator<Item = &f64>| {
+            side.take_while(|&&d| d <= peak)
+                .copied()
+                .fold(peak, f64::min)
+        };
+        // The density is flat only where it has underflowed to zero, so a
+        // peak is strictly above its neighbours
+        (1..density.len() - 1)
+            .filter(|&i| density[i] > density[i - 1] && density[i] > density[i + 1])
+            .filter(|&i| {
+                let left = valley(density[i], &mut density[..i].iter().rev());
+                let right = valley(density[i], &mut density[i + 1..].iter());
+                density[i] - left.max(right) >= MIN_PROMINENCE * highest
+            })
+            .map(|i| self.density[i])
+            .collect()
+    }
+}
+
+/// Sarle's bimodality coefficient, (g^2 + 1) / (k + 3(n - 1)^2 / ((n - 2)(n -
+/// 3))), from the sample skewness g and excess kurtosis k, both corrected for
+/// bias. It is 5/9 for a uniform distribution and approaches 1 for two
+/// well-separated peaks. Needs at least 4 datapoints.
+pub fn bimodality_coefficient(stats: &Stats) -> f64 {
+    let n = stats.n as f64;
+    let g = stats.skewness * (n * (n - 1.)).sqrt() / (n - 2.);
+    let k = ((n + 1.) * stats.kurtosis + 6.) * (n - 1.) / ((n - 2.) * (n - 3.));
+    (g * g + 1.) / (k + 3. * (n - 1.).powi(2) / ((n - 2.) * (n - 3.)))
+}
+
+pub fn print_kde<W>(f: &mut W, stats: &[Stats], opt: &Opt, symbols: &[char]) -> Result<(), Error>
+where
+    W: Write,
+{
+    let show = |x: f64| if opt.log { x.exp() } else { x };
+    for (&symbol, stats) in symbols.iter().skip(1).zip(stats) {
+        let kde = match &stats.kde {
+            Some(kde) => kde,
+            None => {
+                writeln!(f, "{} Density: no spread to estimate it from", symbol)?;
+                continue;
+            }
+        };
+        writeln!(
+            f,
+            "{} Density: Gaussian KDE with {} bandwidth {:.6}{}",
+            symbol,
+            match opt.bandwidth {
+                Bandwidth::Silverman => "Silverman",
+                Bandwidth::Scott => "Scott",
+            },
+            kde.bandwidth,
+            if opt.log { " (of logs)" } else { "" }
+        )?;
+        let modes = kde.modes();
+        writeln!(
+            f,
+            "\t{} mode{}: {}",
+            modes.len(),
+            if modes.len() == 1 { "" } else { "s" },
+            modes
+                .iter()
+                .map(|&(x, _)| format!("{:.6}", show(x)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        if stats.n < 4 {
+            continue;
+        }
+        let coefficient = bimodality_coefficient(stats);
+        writeln!(f, "\tBimodality coefficient {:.6}", coefficient)?;
+        if modes.len() > 1 && coefficient > BIMODALITY_THRESHOLD {
+            writeln!(
+                f,
+                "\tWarning: {} is multimodal, so a single mean or median misrepresents it",
+                symbol
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bimodality_coefficient, kde};
+    use crate::args::Bandwidth;
+    use crate::dist::normal_quantile;
+    use crate::stats::Stats;
+
+    #[test]
+    fn test_kde() {
+        // Evenly spaced normal quantiles, and two copies of them far apart
+        let unimodal: Vec<f64> = (0..50)
+            .map(|i| 10. + normal_quantile((i as f64 + 0.5) / 50.))
+            .collect();
+        let mut bimodal: Vec<f64> = unimodal.iter().flat_map(|&x| [x, x + 10.]).collect();
+        bimodal.sort_unstable_by(|a, b| a.total_cmp(b));
+        let (single, double) = (
+            Stats::from_dataset(&unimodal),
+            Stats::from_dataset(&bimodal),
+        );
+
+        // Silverman's rule is bw.nrd0 in R, while Scott's is 1.06 sd n^(-1/5),
+        // without the IQR that R's bw.nrd also considers
+        let silverman = kde(&unimodal, &single, Bandwidth::Silverman).unwrap();
+        let scott = kde(&unimodal, &single, Bandwidth::Scott).unwrap();
+        let spread = single.stddev.min(single.iqr / 1.34);
+        assert!((silverman.bandwidth - 0.9 * spread * 50f64.powf(-0.2)).abs() < 1e-12);
+        assert!((scott.bandwidth - 1.06 * single.stddev * 50f64.powf(-0.2)).abs() < 1e-12);
+
+        // The density integrates to 1, less the little beyond the grid
+        let step = silverman.density[1].0 - silverman.density[0].0;
+        let area: f64 = silverman.density.iter().map(|p| p.1 * step).sum();
+        assert!(area < 1. && area > 0.995);
+
+        let modes = silverman.modes();
+        assert_eq!(1, modes.len());
+        assert!((modes[0].0 - 10.).abs() < 0.1);
+        let modes = kde(&bimodal, &double, Bandwidth::Silverman)
+            .unwrap()
+            .modes();
+        assert_eq!(2, modes.len());
+        assert!((modes[0].0 - 10.).abs() < 0.1 && (modes[1].0 - 20.).abs() < 0.1);
+
+        assert!(bimodality_coefficient(&single) < 5. / 9.);
+        assert!(bimodality_coefficient(&double) > 5. / 9.);
+    }
+}
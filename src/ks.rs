@@ -0,0 +1,177 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::Opt;
+use crate::effect::cliffs_delta;
+use crate::stats::others;
+use crate::t_table::T_CONFIDENCES;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KsResult {
+    /// The largest vertical distance between the two ECDFs
+    pub d: f64,
+    /// The x-value at which that distance occurs
+    pub x: f64,
+    /// ECDF of the baseline at `x`
+    pub ecdf_baseline: f64,
+    /// ECDF of the other dataset at `x`
+    pub ecdf_other: f64,
+    pub p: f64,
+}
+
+/// Two-sample Kolmogorov-Smirnov test. Both datasets must already be sorted,
+/// as `Dataset::from_reader` leaves them.
+pub fn ks_test(baseline: &[f64], other: &[f64]) -> KsResult {
+    let n1 = baseline.len();
+    let n2 = other.len();
+    let (mut i, mut j) = (0, 0);
+    let mut best = KsResult {
+        d: 0.,
+        x: baseline[0].min(other[0]),
+        ecdf_baseline: 0.,
+        ecdf_other: 0.,
+        p: 1.,
+    };
+    while i < n1 && j < n2 {
+        let x = baseline[i].min(other[j]);
+        // Step past every copy of x in both datasets, so that ties don't
+        // create spurious gaps
+        while i < n1 && baseline[i] <= x {
+            i += 1;
+        }
+        while j < n2 && other[j] <= x {
+            j += 1;
+        }
+        let f1 = i as f64 / n1 as f64;
+        let f2 = j as f64 / n2 as f64;
+        let d = (f1 - f2).abs();
+        if d > best.d {
+            best = KsResult {
+                d,
+                x,
+                ecdf_baseline: f1,
+                ecdf_other: f2,
+                p: 1.,
+            };
+        }
+    }
+    let ne = (n1 * n2) as f64 / (n1 + n2) as f64;
+    // Stephens' small-sample correction to the asymptotic distribution
+    let lambda = (ne.sqrt() + 0.12 + 0.11 / ne.sqrt()) * best.d;
+    best.p = kolmogorov_q(lambda);
+    best
+}
+
+/// Survival function of the Kolmogorov distribution,
+/// `Q(λ) = 2 Σ (-1)^(j-1) exp(-2 j² λ²)`
+fn kolmogorov_q(lambda: f64) -> f64 {
+    if lambda < 1e-3 {
+        return 1.;
+    }
+    let mut sum = 0.;
+    let mut sign = 1.;
+    for j in 1..=100 {
+        let term = sign * 2. * (-2. * (j * j) as f64 * lambda * lambda).exp();
+        sum += term;
+        if term.abs() < 1e-12 {
+            return sum.clamp(0., 1.);
+        }
+        sign = -sign;
+    }
+    // Series failed to converge, which only happens for tiny lambda
+    1.
+}
+
+pub fn print_ks<W, T>(
+    f: &mut W,
+    data: &[T],
+    baseline: usize,
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+    T: AsRef<[f64]>,
+{
+    let confidence_label = T_CONFIDENCES[opt.confidence_level.0];
+    let alpha = 1. - opt.confidence_level.level();
+    let baseline_symbol = symbols[baseline + 1];
+    let others = others(symbols, data, baseline);
+    let baseline = data[baseline].as_ref();
+    for (&symbol, other) in others {
+        let result = ks_test(baseline, other.as_ref());
+        let delta = cliffs_delta(baseline, other.as_ref());
+        if result.p < alpha && delta.abs() < opt.min_effect {
+            writeln!(
+                f,
+                "{} No practical difference at {}% confidence",
+                symbol, confidence_label
+            )?;
+        } else if result.p < alpha {
+            writeln!(
+                f,
+                "{} Difference at {}% confidence",
+                symbol, confidence_label
+            )?;
+        } else {
+            writeln!(
+                f,
+                "{} No difference proven at {}% confidence",
+                symbol, confidence_label
+            )?;
+        }
+        writeln!(
+            f,
+            "\tD = {:.6} at {:.6} (ECDF {} {:.6} vs {} {:.6})",
+            result.d,
+            if opt.log { result.x.exp() } else { result.x },
+            baseline_symbol,
+            result.ecdf_baseline,
+            symbol,
+            result.ecdf_other
+        )?;
+        writeln!(f, "\t(Kolmogorov-Smirnov p = {:.6})", result.p)?;
+        writeln!(f, "\tCliff's delta = {:.6}", delta)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ks_test, print_ks};
+    use crate::{args::Opt, plot::CLASSIC_SYMBOLS};
+
+    #[test]
+    fn test_ks_statistic() {
+        let a = [1., 2., 3., 4., 5.];
+        let b = [3., 4., 5., 6., 7.];
+        let result = ks_test(&a, &b);
+        assert!((result.d - 0.4).abs() < 1e-12);
+        assert_eq!(2., result.x);
+        assert_eq!(0.4, result.ecdf_baseline);
+        assert_eq!(0., result.ecdf_other);
+
+        let same = ks_test(&a, &a);
+        assert_eq!(0., same.d);
+        assert_eq!(1., same.p);
+    }
+
+    #[test]
+    fn test_print_ks() {
+        let data = [
+            vec![1., 2., 3., 4., 5., 6., 7., 8., 9., 10.],
+            vec![11., 12., 13., 14., 15., 16., 17., 18., 19., 20.],
+        ];
+        let mut buf = vec![];
+        print_ks(&mut buf, &data, 0, &Opt::default(), &CLASSIC_SYMBOLS).unwrap();
+        assert_eq!(
+            "+ Difference at 95% confidence
+\tD = 1.000000 at 10.000000 (ECDF x 1.000000 vs + 0.000000)
+\t(Kolmogorov-Smirnov p = 0.000019)
+\tCliff's delta = 1.000000
+",
+            std::str::from_utf8(&buf).unwrap()
+        );
+    }
+}
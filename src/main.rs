@@ -1,18 +1,62 @@
+mod anova;
 mod args;
+mod autocorrelation;
+mod bootstrap;
+mod correlate;
 mod data;
+mod dist;
+mod effect;
+mod equivalence;
 mod err;
+mod kde;
+mod ks;
+mod normality;
+mod onesample;
+mod outliers;
+mod pairwise;
+mod parallel;
+mod permutation;
 mod plot;
+mod power;
+mod ratio;
+mod rng;
+mod sequential;
 mod stats;
+mod streaming;
 mod t_table;
+mod trend;
+mod variance;
 
-use args::Opt;
+use anova::print_omnibus;
+use args::{Baseline, Command, Margin, Opt, Test};
+use autocorrelation::print_autocorrelation;
+use bootstrap::print_bootstrap;
+use correlate::run_correlate;
 use data::{load_data, Dataset};
+use equivalence::print_equivalence;
 use err::*;
+use kde::print_kde;
+use ks::print_ks;
+use normality::print_normality;
+use onesample::print_against;
+use outliers::{detect, print_dropped};
+use pairwise::print_pairwise;
+use parallel::parallel_map;
+use permutation::print_permutation;
 use plot::{plot_graph, print_heading, CLASSIC_SYMBOLS, UNICODE_SYMBOLS};
+use power::print_power;
+use ratio::print_ratio;
+use sequential::run_sequential;
 use stats::*;
+use streaming::run_streaming;
+use trend::print_trend;
+use variance::print_variance;
 
 use clap::Parser;
 
+use std::ffi::OsStr;
+use std::path::Path;
+
 use anyhow::Error;
 use terminal_size::terminal_size;
 
@@ -32,6 +76,25 @@ fn validate_datasets(datasets: &[Dataset]) -> Result<(), Error> {
     Ok(())
 }
 
+/// Finds the index of the dataset chosen with `--baseline`
+fn get_baseline<T: AsRef<Path>>(opt: &Opt, datasets: &[T]) -> Result<usize, Error> {
+    let (idx, provided_baseline) = match &opt.baseline {
+        None => return Ok(0),
+        Some(Baseline::Index(idx)) => (
+            Some(idx - 1).filter(|&idx| idx < datasets.len()),
+            idx.to_string(),
+        ),
+        Some(Baseline::Label(label)) => (
+            datasets.iter().map(AsRef::as_ref).position(|path| {
+                path.to_string_lossy() == label.as_str()
+                    || path.file_name() == Some(OsStr::new(label))
+            }),
+            label.clone(),
+        ),
+    };
+    Ok(idx.ok_or(MinistatFailure::InvalidBaseline { provided_baseline })?)
+}
+
 fn get_symbols(opt: &Opt) -> Vec<char> {
     opt.symbols
         .as_deref()
@@ -45,7 +108,30 @@ fn get_symbols(opt: &Opt) -> Vec<char> {
         })
 }
 
+/// Checks that the options given alongside --log make sense on a log scale
+fn validate_log_options(opt: &Opt) -> Result<(), Error> {
+    if !opt.log {
+        return Ok(());
+    }
+    if let Some(value) = opt.against.filter(|&x| x <= 0.) {
+        return Err(MinistatFailure::NonPositiveAgainst { value }.into());
+    }
+    for (option, margin) in [
+        ("equivalence", opt.equivalence),
+        ("target-effect", opt.target_effect),
+    ] {
+        if let Some(Margin::Absolute(margin)) = margin {
+            return Err(MinistatFailure::AbsoluteLogMargin { option, margin }.into());
+        }
+    }
+    Ok(())
+}
+
 fn run(opt: &Opt) -> Result<(), Error> {
+    validate_log_options(opt)?;
+    if opt.streaming {
+        return run_streaming(opt);
+    }
     let mut stdout = std::io::stdout().lock();
     let symbols = get_symbols(opt);
     if opt.files.len() > symbols.len() - 1 {
@@ -54,32 +140,93 @@ fn run(opt: &Opt) -> Result<(), Error> {
         }
         .into());
     }
-    let datasets = load_data(opt)?;
+    let mut datasets = load_data(opt)?;
     validate_datasets(&datasets)?;
+    if opt.log {
+        for dataset in &mut datasets {
+            dataset.log_transform()?;
+        }
+    }
+
+    let outliers: Vec<_> = datasets.iter().map(|ds| detect(&ds.data, opt)).collect();
+    let mut dropped = vec![];
+    if opt.drop_outliers {
+        for (dataset, outliers) in datasets.iter_mut().zip(outliers.iter().flatten()) {
+            let total = dataset.data.len();
+            dataset.retain(|x| outliers.fences.contains(x));
+            dropped.push((total - dataset.data.len(), total));
+        }
+        validate_datasets(&datasets)?;
+    }
 
-    print_heading(&mut stdout, &datasets, &symbols)?;
-    let stats: Vec<_> = datasets
-        .iter()
-        .map(|dataset| Stats::from_dataset(&dataset.data))
-        .collect();
+    let baseline = get_baseline(opt, &datasets)?;
+    // Only mark the baseline when it was chosen explicitly
+    let marked = opt.baseline.as_ref().map(|_| baseline);
+    print_heading(&mut stdout, &datasets, marked, &symbols)?;
+    print_dropped(&mut stdout, &outliers, &dropped, opt, &symbols)?;
+    let work: Vec<_> = datasets.iter().zip(outliers).collect();
+    let stats: Vec<_> = parallel_map(&work, parallel::jobs(opt), |&(dataset, outliers)| {
+        Stats::from_dataset(&dataset.data)
+            .with_trim(&dataset.data, opt.trim.0)
+            .with_percentiles(&dataset.data, &opt.percentiles.0, opt.quantile_type)
+            .with_outliers(outliers)
+            .with_median_ci(
+                &dataset.data,
+                opt.median_ci.then(|| opt.confidence_level.level()),
+            )
+            .with_effective_n(&dataset.sequence, opt.effective_n)
+            .with_kde(&dataset.data, opt.kde.then_some(opt.bandwidth))
+    });
+    let data: Vec<_> = datasets.iter().map(|x| &x.data[..]).collect();
 
     if !opt.raw_stats && !opt.stats_only {
-        plot_graph(
-            &mut stdout,
-            get_width(opt),
-            opt,
-            &stats,
-            &datasets.into_iter().map(|x| x.data).collect::<Vec<_>>(),
-            &symbols,
-        )?;
+        plot_graph(&mut stdout, get_width(opt), opt, &stats, &data, &symbols)?;
+    }
+    print_stats(&mut stdout, &stats, baseline, opt, &symbols)?;
+    if !opt.raw_stats {
+        match opt.test {
+            Test::Welch => {}
+            Test::Ks => print_ks(&mut stdout, &data, baseline, opt, &symbols)?,
+            Test::Permutation => print_permutation(&mut stdout, &data, baseline, opt, &symbols)?,
+        }
+        let sequences: Vec<_> = datasets.iter().map(|x| &x.sequence[..]).collect();
+        if opt.trend {
+            print_trend(&mut stdout, &sequences, opt, &symbols)?;
+        }
+        if opt.autocorrelation {
+            print_autocorrelation(&mut stdout, &sequences, opt, &symbols)?;
+        }
+        if opt.kde {
+            print_kde(&mut stdout, &stats, opt, &symbols)?;
+        }
+        if let Some(reference) = opt.against {
+            print_against(&mut stdout, &stats, &data, reference, opt, &symbols)?;
+        }
+        if opt.normality {
+            print_normality(&mut stdout, &stats, &data, opt, &symbols)?;
+        }
+        if opt.omnibus {
+            print_omnibus(&mut stdout, &stats, &data, opt, &symbols)?;
+        }
+        if opt.pairwise {
+            print_pairwise(&mut stdout, &stats, opt, &symbols)?;
+        }
+        if opt.power {
+            print_power(&mut stdout, &stats, baseline, opt, &symbols)?;
+        }
+        if opt.test_variance {
+            print_variance(&mut stdout, &stats, &data, baseline, opt, &symbols)?;
+        }
+        if opt.ratio {
+            print_ratio(&mut stdout, &stats, &data, baseline, opt, &symbols)?;
+        }
+        if let Some(margin) = opt.equivalence {
+            print_equivalence(&mut stdout, &stats, baseline, margin, opt, &symbols)?;
+        }
+        if let Some(resamples) = opt.bootstrap {
+            print_bootstrap(&mut stdout, &data, baseline, resamples.get(), opt, &symbols)?;
+        }
     }
-    print_stats(
-        &mut stdout,
-        &stats,
-        opt.confidence_level.0,
-        opt.raw_stats,
-        &symbols,
-    )?;
 
     Ok(())
 }
@@ -87,7 +234,11 @@ fn run(opt: &Opt) -> Result<(), Error> {
 fn main() -> Result<(), Error> {
     let opt = Opt::parse();
 
-    run(&opt)?;
+    match &opt.command {
+        Some(Command::Sequential(seq_opt)) => run_sequential(&opt, seq_opt)?,
+        Some(Command::Correlate(corr_opt)) => run_correlate(&opt, corr_opt)?,
+        None => run(&opt)?,
+    }
     Ok(())
 }
 
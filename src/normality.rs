@@ -0,0 +1,267 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::Opt;
+use crate::dist::{normal_cdf, normal_quantile};
+use crate::stats::Stats;
+
+/// Largest dataset the Shapiro-Wilk approximation is valid for
+pub const SHAPIRO_WILK_MAX_N: usize = 5000;
+
+/// Beyond this A^2, where the p-value approximation reaches its minimum and
+/// would start rising again, the p-value is taken to be 0
+const ANDERSON_DARLING_MAX_A2: f64 = 5.709 / (2. * 0.0186);
+
+/// Normality is "strongly" rejected, and a warning printed, below this p-value
+const WARNING_P: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normality {
+    pub name: &'static str,
+    /// W for Shapiro-Wilk, or the small-sample adjusted A^2 for
+    /// Anderson-Darling
+    pub statistic: f64,
+    pub p: f64,
+}
+
+/// Evaluates the polynomial with coefficients `c`, lowest order first
+fn poly(c: &[f64], x: f64) -> f64 {
+    c.iter().rev().fold(0., |acc, &c| acc * x + c)
+}
+
+/// The Shapiro-Wilk test of sorted data, with 3 <= n <= 5000, using Royston's
+/// (1992, 1995) approximations for the coefficients and p-value (algorithm
+/// AS R94, as in R's `shapiro.test()`)
+pub fn shapiro_wilk(sorted: &[f64]) -> Normality {
+    let n = sorted.len();
+    let nf = n as f64;
+    let half = n / 2;
+
+    // The coefficients are antisymmetric, so only the lower half is needed
+    let mut a = vec![0.; half];
+    if n == 3 {
+        a[0] = 0.5f64.sqrt();
+    } else {
+        let m: Vec<f64> = (1..=half)
+            .map(|i| -normal_quantile((i as f64 - 0.375) / (nf + 0.25)))
+            .collect();
+        let summ2 = 2. * m.iter().map(|m| m * m).sum::<f64>();
+        let ssumm2 = summ2.sqrt();
+        let rsn = 1. / nf.sqrt();
+        let a1 = m[0] / ssumm2
+            + poly(
+                &[0., 0.221157, -0.147981, -2.07119, 4.434685, -2.706056],
+                rsn,
+            );
+        a[0] = a1;
+        let (first, fac) = if n > 5 {
+            let a2 = m[1] / ssumm2
+                + poly(
+                    &[0., 0.042981, -0.293762, -1.752461, 5.682633, -3.582633],
+                    rsn,
+                );
+            a[1] = a2;
+            let fac = ((summ2 - 2. * m[0] * m[0] - 2. * m[1] * m[1])
+                / (1. - 2. * a1 * a1 - 2. * a2 * a2))
+                .sqrt();
+            (2, fac)
+        } else {
+            let fac = ((summ2 - 2. * m[0] * m[0]) / (1. - 2. * a1 * a1)).sqrt();
+            (1, fac)
+        };
+        for i in first..half {
+            a[i] = m[i] / fac;
+        }
+    }
+
+    let mean = sorted.iter().sum::<f64>() / nf;
+    let ss: f64 = sorted.iter().map(|x| (x - mean).powi(2)).sum();
+    let b: f64 = a
+        .iter()
+        .enumerate()
+        .map(|(i, a)| a * (sorted[n - 1 - i] - sorted[i]))
+        .sum();
+    let w = (b * b / ss).min(1.);
+
+    let p = if n == 3 {
+        // Exact
+        (6. / std::f64::consts::PI * (w.sqrt().asin() - std::f64::consts::PI / 3.)).max(0.)
+    } else {
+        // Normalising transformations of W, fitted separately for small n
+        let (y, mu, sigma) = if n <= 11 {
+            let gamma = poly(&[-2.273, 0.459], nf);
+            let y = (1. - w).ln();
+            if y >= gamma {
+                return Normality {
+                    name: "Shapiro-Wilk",
+                    statistic: w,
+                    p: 0.,
+                };
+            }
+            (
+                -(gamma - y).ln(),
+                poly(&[0.544, -0.39978, 0.025054, -6.714e-4], nf),
+                poly(&[1.3822, -0.77857, 0.062767, -0.0020322], nf).exp(),
+            )
+        } else {
+            let ln_n = nf.ln();
+            (
+                (1. - w).ln(),
+                poly(&[-1.5861, -0.31082, -0.083751, 0.0038915], ln_n),
+                poly(&[-0.4803, -0.082676, 0.0030302], ln_n).exp(),
+            )
+        };
+        1. - normal_cdf((y - mu) / sigma)
+    };
+    Normality {
+        name: "Shapiro-Wilk",
+        statistic: w,
+        p,
+    }
+}
+
+/// The Anderson-Darling test of sorted data against a normal distribution
+/// with the sample's mean and standard deviation, with D'Agostino and
+/// Stephens' (1986) approximate p-values
+pub fn anderson_darling(sorted: &[f64], stats: &Stats) -> Normality {
+    let n = sorted.len();
+    let nf = n as f64;
+    // Both tails of the fitted normal at each datapoint, kept above zero so
+    // that the logs stay finite however far out the data lies
+    let tail = |z: f64| normal_cdf(z).max(f64::MIN_POSITIVE).ln();
+    let z: Vec<f64> = sorted
+        .iter()
+        .map(|x| (x - stats.mean) / stats.stddev)
+        .collect();
+    let sum: f64 = (0..n)
+        .map(|i| (2 * i + 1) as f64 * (tail(z[i]) + tail(-z[n - 1 - i])))
+        .sum();
+    let a2 = (-nf - sum / nf) * (1. + 0.75 / nf + 2.25 / (nf * nf));
+    let p = if a2 > ANDERSON_DARLING_MAX_A2 {
+        0.
+    } else if a2 >= 0.6 {
+        (1.2937 - 5.709 * a2 + 0.0186 * a2 * a2).exp()
+    } else if a2 >= 0.34 {
+        (0.9177 - 4.279 * a2 - 1.38 * a2 * a2).exp()
+    } else if a2 >= 0.2 {
+        1. - (-8.318 + 42.796 * a2 - 59.938 * a2 * a2).exp()
+    } else {
+        1. - (-13.436 + 101.14 * a2 - 223.73 * a2 * a2).exp()
+    };
+    Normality {
+        name: "Anderson-Darling",
+        statistic: a2,
+        p: p.clamp(0., 1.),
+    }
+}
+
+/// Tests sorted data for normality, with Shapiro-Wilk where it applies and
+/// Anderson-Darling for larger datasets
+pub fn normality(sorted: &[f64], stats: &Stats) -> Normality {
+    if sorted.len() <= SHAPIRO_WILK_MAX_N {
+        shapiro_wilk(sorted)
+    } else {
+        anderson_darling(sorted, stats)
+    }
+}
+
+pub fn print_normality<W, T>(
+    f: &mut W,
+    stats: &[Stats],
+    data: &[T],
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+    T: AsRef<[f64]>,
+{
+    for ((&symbol, stats), data) in symbols.iter().skip(1).zip(stats).zip(data) {
+        let result = normality(data.as_ref(), stats);
+        writeln!(
+            f,
+            "{} Normality: {} {} = {:.6}, p = {:.6}",
+            symbol,
+            result.name,
+            if result.name == "Shapiro-Wilk" {
+                "W"
+            } else {
+                "A^2"
+            },
+            result.statistic,
+            result.p
+        )?;
+        writeln!(
+            f,
+            "\tSkewness = {:.6}, excess kurtosis = {:.6}",
+            stats.skewness, stats.kurtosis
+        )?;
+        if result.p < WARNING_P {
+            writeln!(
+                f,
+                "\tWarning: {} is far from normal{}; consider a rank-based test such as --test ks",
+                symbol,
+                if opt.log { " even on a log scale" } else { "" }
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{anderson_darling, shapiro_wilk};
+    use crate::dist::normal_quantile;
+    use crate::stats::Stats;
+
+    #[test]
+    fn test_normality() {
+        // Three equally spaced points fit a normal perfectly
+        let result = shapiro_wilk(&[1., 2., 3.]);
+        assert!((result.statistic - 1.).abs() < 1e-12);
+        assert!((result.p - 1.).abs() < 1e-12);
+
+        // shapiro.test(1:10) in R
+        let data: Vec<f64> = (1..=10).map(|x| x as f64).collect();
+        let result = shapiro_wilk(&data);
+        assert!((result.statistic - 0.97016).abs() < 1e-5);
+        assert!((result.p - 0.8924).abs() < 1e-4);
+
+        // Expected normal order statistics are about as normal as data gets,
+        // while exponentially growing data is clearly skewed
+        let normal = |n: usize| -> Vec<f64> {
+            (1..=n)
+                .map(|i| normal_quantile((i as f64 - 0.375) / (n as f64 + 0.25)))
+                .collect()
+        };
+        for n in [4, 8, 20, 50] {
+            let result = shapiro_wilk(&normal(n));
+            assert!(
+                result.statistic > 0.95 && result.p > 0.5,
+                "{} {:?}",
+                n,
+                result
+            );
+        }
+        let normal = normal(50);
+        let skewed: Vec<f64> = (0..50).map(|i| 1.1f64.powi(i)).collect();
+        let result = shapiro_wilk(&skewed);
+        assert!(result.p < 0.001);
+        assert!(Stats::from_dataset(&skewed).skewness > 1.);
+
+        let result = anderson_darling(&normal, &Stats::from_dataset(&normal));
+        assert!(result.p > 0.5);
+        let result = anderson_darling(&skewed, &Stats::from_dataset(&skewed));
+        assert!(result.p < 0.001);
+
+        // An outlier so far out that the fitted normal's tail underflows
+        let mut saturated: Vec<f64> = (1..6000)
+            .map(|i| normal_quantile(i as f64 / 6000.))
+            .collect();
+        saturated.push(1e9);
+        let result = anderson_darling(&saturated, &Stats::from_dataset(&saturated));
+        assert!(result.statistic.is_finite() && result.statistic > 1000.);
+        assert_eq!(0., result.p);
+    }
+}
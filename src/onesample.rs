@@ -0,0 +1,206 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::anova::rank_all;
+use crate::args::{AgainstTest, Opt};
+use crate::dist::{binomial_cdf, normal_cdf, t_cdf};
+use crate::stats::{quantile_ci, t_critical, Stats};
+use crate::t_table::T_CONFIDENCES;
+
+/// Largest number of nonzero differences for which the Wilcoxon signed-rank
+/// test's p-value is computed exactly, if there are no ties
+const WILCOXON_EXACT_N: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OneSample {
+    /// t for the t-test, the number of datapoints above the reference value
+    /// for the sign test, or the sum of the positive ranks for Wilcoxon
+    pub statistic: f64,
+    pub p: f64,
+}
+
+/// One-sample t-test of whether the dataset's mean differs from `reference`
+pub fn t_test(stats: &Stats, reference: f64) -> OneSample {
+    let dof = (stats.n - 1) as f64;
+    let t = (stats.mean - reference) / (stats.stddev / (stats.n as f64).sqrt());
+    OneSample {
+        statistic: t,
+        p: (2. * (1. - t_cdf(t.abs(), dof))).min(1.),
+    }
+}
+
+/// Sign test of whether the dataset's median differs from `reference`,
+/// ignoring datapoints equal to it
+pub fn sign_test(data: &[f64], reference: f64) -> OneSample {
+    let above = data.iter().filter(|&&x| x > reference).count() as f64;
+    let below = data.iter().filter(|&&x| x < reference).count() as f64;
+    let n = above + below;
+    let tail = binomial_cdf(above, n, 0.5).min(binomial_cdf(below, n, 0.5));
+    OneSample {
+        statistic: above,
+        p: (2. * tail).min(1.),
+    }
+}
+
+/// Wilcoxon signed-rank test of whether the dataset is symmetric about
+/// `reference`, ignoring datapoints equal to it. The p-value is exact for
+/// small datasets without ties, and otherwise uses the normal approximation
+/// with corrections for ties and continuity.
+pub fn wilcoxon(data: &[f64], reference: f64) -> OneSample {
+    let differences: Vec<f64> = data
+        .iter()
+        .map(|x| x - reference)
+        .filter(|&d| d != 0.)
+        .collect();
+    let n = differences.len();
+    if n == 0 {
+        return OneSample {
+            statistic: 0.,
+            p: 1.,
+        };
+    }
+    let magnitudes: Vec<f64> = differences.iter().map(|d| d.abs()).collect();
+    let (ranks, ties) = rank_all(&[magnitudes]);
+    let w: f64 = differences
+        .iter()
+        .zip(&ranks[0])
+        .filter(|(&d, _)| d > 0.)
+        .map(|(_, r)| r)
+        .sum();
+    let nf = n as f64;
+    let mean = nf * (nf + 1.) / 4.;
+    let p = if n <= WILCOXON_EXACT_N && ties == 0. {
+        // Count the subsets of the ranks 1..=n with each possible sum
+        let max = n * (n + 1) / 2;
+        let mut counts = vec![0f64; max + 1];
+        counts[0] = 1.;
+        for rank in 1..=n {
+            for sum in (rank..=max).rev() {
+                counts[sum] += counts[sum - rank];
+            }
+        }
+        let total = 2f64.powi(n as i32);
+        let w = w as usize;
+        let lower: f64 = counts[..=w].iter().sum::<f64>() / total;
+        let upper: f64 = counts[w..].iter().sum::<f64>() / total;
+        2. * lower.min(upper)
+    } else {
+        let var = nf * (nf + 1.) * (2. * nf + 1.) / 24. - ties / 48.;
+        let z = ((w - mean).abs() - 0.5).max(0.) / var.sqrt();
+        2. * (1. - normal_cdf(z))
+    };
+    OneSample {
+        statistic: w,
+        p: p.min(1.),
+    }
+}
+
+pub fn print_against<W, T>(
+    f: &mut W,
+    stats: &[Stats],
+    data: &[T],
+    reference: f64,
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+    T: AsRef<[f64]>,
+{
+    let confidence_idx = opt.confidence_level.0;
+    let confidence_label = T_CONFIDENCES[confidence_idx];
+    let alpha = 1. - opt.confidence_level.level();
+    // With --log, the data are logs, so compare them to the log of the
+    // reference and show the results back on the original scale
+    let scaled = if opt.log { reference.ln() } else { reference };
+    let show = |x: f64| if opt.log { x.exp() } else { x };
+    for ((&symbol, stats), data) in symbols.iter().skip(1).zip(stats).zip(data) {
+        let data = data.as_ref();
+        let (location, estimate, (low, high), result, name) = match opt.against_test {
+            AgainstTest::T => {
+                let half_width = t_critical((stats.n - 1) as f64, confidence_idx) * stats.stddev
+                    / (stats.n as f64).sqrt();
+                (
+                    if opt.log { "Geo mean" } else { "Mean" },
+                    stats.mean,
+                    (stats.mean - half_width, stats.mean + half_width),
+                    t_test(stats, scaled),
+                    "one-sample t",
+                )
+            }
+            AgainstTest::Sign => (
+                "Median",
+                stats.median,
+                quantile_ci(data, 0.5, opt.confidence_level.level()),
+                sign_test(data, scaled),
+                "sign test, above",
+            ),
+            AgainstTest::Wilcoxon => (
+                "Median",
+                stats.median,
+                quantile_ci(data, 0.5, opt.confidence_level.level()),
+                wilcoxon(data, scaled),
+                "Wilcoxon signed-rank V",
+            ),
+        };
+        writeln!(
+            f,
+            "{} {} compared to {} at {}% confidence",
+            symbol, location, reference, confidence_label
+        )?;
+        writeln!(
+            f,
+            "\t{:.6} [{:.6}, {:.6}]",
+            show(estimate),
+            show(low),
+            show(high)
+        )?;
+        let verdict = if result.p >= alpha {
+            "no difference proven"
+        } else if estimate > scaled {
+            "above"
+        } else {
+            "below"
+        };
+        writeln!(
+            f,
+            "\t({} = {:.6}, p = {:.6}): {}",
+            name, result.statistic, result.p, verdict
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sign_test, t_test, wilcoxon};
+    use crate::stats::Stats;
+
+    #[test]
+    fn test_one_sample() {
+        let data = [1., 2., 3., 4., 5., 6., 7., 8., 9., 10.];
+        // t.test(1:10, mu = 4)
+        let result = t_test(&Stats::from_dataset(&data), 4.);
+        assert!((result.statistic - 1.5667).abs() < 1e-4);
+        assert!((result.p - 0.1516).abs() < 1e-4);
+
+        // 5.5 has 5 datapoints above it and 5 below, so the sign test can't
+        // tell, while 2.5 has 8 above and 2 below: binom.test(8, 10)
+        assert_eq!(1., sign_test(&data, 5.5).p);
+        let result = sign_test(&data, 2.5);
+        assert_eq!(8., result.statistic);
+        assert!((result.p - 0.109375).abs() < 1e-9);
+
+        // wilcox.test(1:10, mu = 2.4): exact, with only 7 of the 1024 sign
+        // patterns giving the negative ranks a sum of 4 or less
+        let result = wilcoxon(&data, 2.4);
+        assert_eq!(51., result.statistic);
+        assert!((result.p - 14. / 1024.).abs() < 1e-12);
+        // wilcox.test(1:10, mu = 3, exact = FALSE): a zero difference is
+        // dropped and two pairs of the rest tie
+        let result = wilcoxon(&data, 3.);
+        assert_eq!(40., result.statistic);
+        assert!((result.p - 0.04364).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,198 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::{Opt, OutlierMethod};
+use crate::dist::t_quantile;
+use crate::stats::{quantile, Stats};
+
+/// Points outside `low..=high` are outliers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fences {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Fences {
+    pub fn contains(&self, x: f64) -> bool {
+        self.low <= x && x <= self.high
+    }
+}
+
+/// Finds the fences outside of which points of `sorted` are flagged as
+/// outliers. `k` is the multiple of the IQR or MAD for Tukey's fences and the
+/// MAD rule, while Grubbs' test rejects points at significance `alpha`.
+/// Returns `None` when the IQR or MAD is zero, as happens when over half the
+/// datapoints are tied, since the fences would then flag every other point.
+pub fn fences(sorted: &[f64], method: OutlierMethod, k: Option<f64>, alpha: f64) -> Option<Fences> {
+    match method {
+        OutlierMethod::Tukey => {
+            let k = k.unwrap_or(1.5);
+            let q1 = quantile(sorted, 0.25);
+            let q3 = quantile(sorted, 0.75);
+            (q3 > q1).then_some(Fences {
+                low: q1 - k * (q3 - q1),
+                high: q3 + k * (q3 - q1),
+            })
+        }
+        OutlierMethod::Mad => {
+            let k = k.unwrap_or(3.);
+            let stats = Stats::from_dataset(sorted);
+            (stats.mad > 0.).then_some(Fences {
+                low: stats.median - k * stats.mad,
+                high: stats.median + k * stats.mad,
+            })
+        }
+        OutlierMethod::Grubbs => Some(grubbs(sorted, alpha)),
+    }
+}
+
+/// Grubbs' test, applied repeatedly: the most extreme point is rejected if it
+/// is significantly far from the mean, and the test is rerun on the rest.
+fn grubbs(sorted: &[f64], alpha: f64) -> Fences {
+    let (mut lo, mut hi) = (0, sorted.len());
+    while hi - lo > 3 {
+        let kept = &sorted[lo..hi];
+        let n = kept.len() as f64;
+        let stats = Stats::from_dataset(kept);
+        if stats.stddev == 0. {
+            break;
+        }
+        let low_dev = stats.mean - kept[0];
+        let high_dev = kept[kept.len() - 1] - stats.mean;
+        let g = low_dev.max(high_dev) / stats.stddev;
+        let t = t_quantile(1. - alpha / (2. * n), n - 2.);
+        let g_critical = (n - 1.) / n.sqrt() * (t * t / (n - 2. + t * t)).sqrt();
+        if g <= g_critical {
+            break;
+        }
+        if low_dev > high_dev {
+            lo += 1;
+        } else {
+            hi -= 1;
+        }
+    }
+    Fences {
+        low: sorted[lo],
+        high: sorted[hi - 1],
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outliers {
+    pub fences: Fences,
+    /// How many points lay outside the fences
+    pub count: usize,
+    /// Whether detection was skipped because the data's spread was zero, in
+    /// which case the fences hold every point
+    pub no_spread: bool,
+}
+
+/// Looks for outliers in `sorted` using the method chosen with `--outliers`
+pub fn detect(sorted: &[f64], opt: &Opt) -> Option<Outliers> {
+    let method = opt.outliers?;
+    let alpha = 1. - opt.confidence_level.level();
+    let found = fences(sorted, method, opt.outlier_k.map(|k| k.0), alpha);
+    let fences = found.unwrap_or(Fences {
+        low: f64::NEG_INFINITY,
+        high: f64::INFINITY,
+    });
+    Some(Outliers {
+        fences,
+        count: sorted.iter().filter(|&&x| !fences.contains(x)).count(),
+        no_spread: found.is_none(),
+    })
+}
+
+pub fn method_name(method: OutlierMethod) -> &'static str {
+    match method {
+        OutlierMethod::Tukey => "Tukey's fences",
+        OutlierMethod::Mad => "median absolute deviation",
+        OutlierMethod::Grubbs => "Grubbs' test",
+    }
+}
+
+/// Reports how many points were dropped from each dataset, and warns about
+/// datasets whose outliers couldn't be looked for
+pub fn print_dropped<W>(
+    f: &mut W,
+    outliers: &[Option<Outliers>],
+    dropped: &[(usize, usize)],
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+{
+    let method = match opt.outliers {
+        Some(method) => method,
+        None => return Ok(()),
+    };
+    for (&symbol, outliers) in symbols.iter().skip(1).zip(outliers.iter().flatten()) {
+        if outliers.no_spread {
+            writeln!(
+                f,
+                "{} Warning: over half the datapoints are tied, leaving no spread to flag \
+                 outliers with ({})",
+                symbol,
+                method_name(method)
+            )?;
+        }
+    }
+    for (&symbol, &(dropped, total)) in symbols.iter().skip(1).zip(dropped.iter()) {
+        writeln!(
+            f,
+            "{} Dropped {} of {} datapoints as outliers ({})",
+            symbol,
+            dropped,
+            total,
+            method_name(method)
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{detect, fences};
+    use crate::args::Opt;
+    use crate::args::{OutlierK, OutlierMethod};
+
+    #[test]
+    fn test_fences() {
+        let data = [1., 2., 3., 4., 5., 6., 7., 8., 9., 100.];
+        let tukey = fences(&data, OutlierMethod::Tukey, None, 0.05).unwrap();
+        assert_eq!(3.25 - 1.5 * 4.5, tukey.low);
+        assert_eq!(7.75 + 1.5 * 4.5, tukey.high);
+        assert!(!tukey.contains(100.) && tukey.contains(9.));
+
+        let mad = fences(&data, OutlierMethod::Mad, Some(2.), 0.05).unwrap();
+        assert!((mad.high - (5.5 + 2. * 2.5 * 1.4826)).abs() < 1e-12);
+        // Multipliers that would collapse or invert the fences are rejected
+        for k in ["0", "-1", "NaN", "inf"] {
+            assert!(k.parse::<OutlierK>().is_err(), "{}", k);
+        }
+
+        let grubbs = fences(&data, OutlierMethod::Grubbs, None, 0.05).unwrap();
+        assert_eq!(1., grubbs.low);
+        assert_eq!(9., grubbs.high);
+
+        let clean = [1., 2., 3., 4., 5., 6., 7., 8., 9., 10.];
+        let grubbs = fences(&clean, OutlierMethod::Grubbs, None, 0.05).unwrap();
+        assert_eq!(1., grubbs.low);
+        assert_eq!(10., grubbs.high);
+
+        // Mostly tied data has no spread, so nothing is flagged rather than
+        // everything but the ties
+        let tied = [1., 5., 5., 5., 5., 5., 5., 9.];
+        assert!(fences(&tied, OutlierMethod::Mad, None, 0.05).is_none());
+        assert!(fences(&tied, OutlierMethod::Tukey, None, 0.05).is_none());
+        let opt = Opt {
+            outliers: Some(OutlierMethod::Mad),
+            ..Opt::default()
+        };
+        let outliers = detect(&tied, &opt).unwrap();
+        assert!(outliers.no_spread && outliers.fences.contains(9.));
+        assert_eq!(0, outliers.count);
+    }
+}
@@ -0,0 +1,169 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::{Correction, Opt};
+use crate::dist::t_cdf;
+use crate::stats::{welch, Stats};
+use crate::t_table::T_CONFIDENCES;
+
+/// Adjusts `p`, the p-values of a family of tests, for multiple comparisons.
+/// Bonferroni and Holm control the family-wise error rate, while
+/// Benjamini-Hochberg controls the false discovery rate.
+pub fn adjust(p: &[f64], correction: Correction) -> Vec<f64> {
+    let m = p.len() as f64;
+    let mut order: Vec<usize> = (0..p.len()).collect();
+    order.sort_unstable_by(|&a, &b| p[a].total_cmp(&p[b]));
+    let mut adjusted = vec![0.; p.len()];
+    match correction {
+        Correction::Bonferroni => {
+            for (adj, &p) in adjusted.iter_mut().zip(p) {
+                *adj = (p * m).min(1.);
+            }
+        }
+        Correction::Holm => {
+            // Step down from the smallest p-value, keeping the adjusted values
+            // monotonic
+            let mut running_max: f64 = 0.;
+            for (rank, &i) in order.iter().enumerate() {
+                running_max = running_max.max((p[i] * (m - rank as f64)).min(1.));
+                adjusted[i] = running_max;
+            }
+        }
+        Correction::Bh => {
+            // Step up from the largest p-value
+            let mut running_min: f64 = 1.;
+            for (rank, &i) in order.iter().enumerate().rev() {
+                running_min = running_min.min(p[i] * m / (rank + 1) as f64);
+                adjusted[i] = running_min;
+            }
+        }
+    }
+    adjusted
+}
+
+pub fn correction_name(correction: Correction) -> &'static str {
+    match correction {
+        Correction::Bonferroni => "Bonferroni",
+        Correction::Holm => "Holm",
+        Correction::Bh => "Benjamini-Hochberg",
+    }
+}
+
+/// Welch's t-test p-value for the difference between two datasets' means
+fn welch_p(a: &Stats, b: &Stats) -> f64 {
+    let (val, dof) = welch(a, b);
+    let t = (b.mean - a.mean) / val.sqrt();
+    2. * (1. - t_cdf(t.abs(), dof))
+}
+
+/// Compares every pair of datasets with Welch's t-test. Returns a matrix of
+/// the corrected p-values, whose diagonal is NaN.
+pub fn pairwise_p(stats: &[Stats], correction: Correction) -> Vec<Vec<f64>> {
+    let pairs: Vec<(usize, usize)> = (0..stats.len())
+        .flat_map(|i| (i + 1..stats.len()).map(move |j| (i, j)))
+        .collect();
+    let p: Vec<f64> = pairs
+        .iter()
+        .map(|&(i, j)| welch_p(&stats[i], &stats[j]))
+        .collect();
+    let mut matrix = vec![vec![f64::NAN; stats.len()]; stats.len()];
+    for (&(i, j), p) in pairs.iter().zip(adjust(&p, correction)) {
+        matrix[i][j] = p;
+        matrix[j][i] = p;
+    }
+    matrix
+}
+
+pub fn print_pairwise<W>(
+    f: &mut W,
+    stats: &[Stats],
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+{
+    let alpha = 1. - opt.confidence_level.level();
+    let p = pairwise_p(stats, opt.correction);
+    let symbols = &symbols[1..=stats.len()];
+    let print_header = |f: &mut W| {
+        write!(f, " ")?;
+        for symbol in symbols {
+            write!(f, " {:>13}", symbol)?;
+        }
+        writeln!(f)
+    };
+
+    // With --log, a difference in the means of logs is a ratio of geometric
+    // means
+    if opt.log {
+        writeln!(f, "Pairwise ratios of geometric means (column / row)")?;
+    } else {
+        writeln!(f, "Pairwise differences in means (column - row)")?;
+    }
+    print_header(f)?;
+    for (row, &symbol) in stats.iter().zip(symbols) {
+        write!(f, "{}", symbol)?;
+        for column in stats {
+            let difference = column.mean - row.mean;
+            write!(
+                f,
+                " {:13.6}",
+                if opt.log {
+                    difference.exp()
+                } else {
+                    difference
+                }
+            )?;
+        }
+        writeln!(f)?;
+    }
+
+    writeln!(
+        f,
+        "Pairwise Welch's t-test p-values ({} corrected, * significant at {}% confidence)",
+        correction_name(opt.correction),
+        T_CONFIDENCES[opt.confidence_level.0]
+    )?;
+    print_header(f)?;
+    for (row, &symbol) in p.iter().zip(symbols) {
+        write!(f, "{}", symbol)?;
+        for &p in row {
+            if p.is_nan() {
+                write!(f, " {:>13}", "-")?;
+            } else {
+                write!(f, " {:12.6}{}", p, if p < alpha { '*' } else { ' ' })?;
+            }
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::adjust;
+    use crate::args::Correction;
+
+    fn assert_close(expected: &[f64], actual: &[f64]) {
+        assert_eq!(expected.len(), actual.len());
+        for (e, a) in expected.iter().zip(actual) {
+            assert!((e - a).abs() < 1e-12, "{:?} != {:?}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_adjust() {
+        let p = [0.01, 0.04, 0.03, 0.005];
+        assert_close(
+            &[0.04, 0.16, 0.12, 0.02],
+            &adjust(&p, Correction::Bonferroni),
+        );
+        // Sorted: 0.005 * 4, 0.01 * 3, 0.03 * 2, 0.04 * 1, then made monotonic
+        assert_close(&[0.03, 0.06, 0.06, 0.02], &adjust(&p, Correction::Holm));
+        // Sorted: 0.005 * 4/1, 0.01 * 4/2, 0.03 * 4/3, 0.04 * 4/4
+        assert_close(&[0.02, 0.04, 0.04, 0.02], &adjust(&p, Correction::Bh));
+        assert_close(&[1., 1.], &adjust(&[0.5, 0.9], Correction::Bonferroni));
+    }
+}
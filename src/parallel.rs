@@ -0,0 +1,77 @@
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::args::Opt;
+
+/// Number of threads to use: --jobs if given, otherwise one per CPU
+pub fn jobs(opt: &Opt) -> usize {
+    opt.jobs
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+}
+
+/// Applies `f` to every item on up to `jobs` threads, returning the results in
+/// the items' order. Each thread takes the next unclaimed item when it
+/// finishes one, so a few large items don't leave the others idle. With a
+/// single job or item, everything runs on the calling thread.
+pub fn parallel_map<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let jobs = jobs.min(items.len());
+    if jobs <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let mut done: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = vec![];
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(i) {
+                            Some(item) => done.push((i, f(item))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| match worker.join() {
+                Ok(done) => done,
+                Err(panic) => std::panic::resume_unwind(panic),
+            })
+            .collect()
+    });
+    done.sort_unstable_by_key(|&(i, _)| i);
+    done.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::parallel_map;
+
+    #[test]
+    fn test_parallel_map() {
+        // Uneven work still comes back in order
+        let items: Vec<u64> = (0..100).collect();
+        let squares = parallel_map(&items, 8, |&x| {
+            std::thread::sleep(std::time::Duration::from_micros((x % 7) * 100));
+            x * x
+        });
+        assert_eq!(items.iter().map(|x| x * x).collect::<Vec<_>>(), squares);
+        assert_eq!(squares, parallel_map(&items, 1, |&x| x * x));
+        assert!(parallel_map(&items[..0], 4, |&x| x).is_empty());
+
+        // The first failure in the items' order is reported, whichever
+        // thread got there first
+        let results = parallel_map(&items, 8, |&x| if x % 30 == 29 { Err(x) } else { Ok(x) });
+        assert_eq!(Err(29), results.into_iter().collect::<Result<Vec<_>, _>>());
+    }
+}
@@ -0,0 +1,275 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::{Location, Opt};
+use crate::effect::{cliffs_delta, hedges_g};
+use crate::rng::Rng;
+use crate::stats::{others, Stats};
+use crate::t_table::T_CONFIDENCES;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PermutationResult {
+    /// Observed difference, other minus baseline
+    pub difference: f64,
+    pub p: f64,
+    /// How many arrangements were evaluated
+    pub permutations: u64,
+    /// Whether every possible arrangement was evaluated
+    pub exact: bool,
+}
+
+impl Location {
+    fn compute(&self, values: &mut [f64]) -> f64 {
+        match self {
+            Location::Mean => values.iter().sum::<f64>() / values.len() as f64,
+            Location::Median => {
+                let n = values.len();
+                values.sort_unstable_by(|a, b| a.total_cmp(b));
+                if n % 2 == 1 {
+                    values[n / 2]
+                } else {
+                    (values[n / 2] + values[n / 2 - 1]) / 2.
+                }
+            }
+        }
+    }
+}
+
+/// Number of ways to choose `k` items from `n`, saturating at `u64::MAX`
+fn choose(n: usize, k: usize) -> u64 {
+    let k = k.min(n - k);
+    let mut rv: u128 = 1;
+    for i in 0..k {
+        rv = rv * (n - i) as u128 / (i + 1) as u128;
+        if rv > u64::MAX as u128 {
+            return u64::MAX;
+        }
+    }
+    rv as u64
+}
+
+/// Two-sided permutation test for a difference in location. If there are no
+/// more than `permutations` ways to split the pooled data, all of them are
+/// tried and the p-value is exact; otherwise `permutations` random splits are
+/// drawn from `rng`.
+pub fn permutation_test(
+    baseline: &[f64],
+    other: &[f64],
+    location: Location,
+    permutations: u64,
+    rng: &mut Rng,
+) -> PermutationResult {
+    let n1 = baseline.len();
+    let pooled: Vec<f64> = baseline.iter().chain(other.iter()).copied().collect();
+    let n = pooled.len();
+    let mut a = Vec::with_capacity(n1);
+    let mut b = Vec::with_capacity(n - n1);
+    let diff = |a: &mut Vec<f64>, b: &mut Vec<f64>| location.compute(b) - location.compute(a);
+
+    a.extend_from_slice(baseline);
+    b.extend_from_slice(other);
+    let observed = diff(&mut a, &mut b);
+    // Allow for rounding error when the permuted statistic equals the observed
+    let threshold = observed.abs() * (1. - 1e-12);
+
+    let arrangements = choose(n, n1);
+    let mut extreme = 0u64;
+    if arrangements <= permutations {
+        // Walk through every subset of n1 indices, in lexicographic order
+        let mut idx: Vec<usize> = (0..n1).collect();
+        loop {
+            a.clear();
+            b.clear();
+            let mut next = idx.iter().peekable();
+            for (i, &v) in pooled.iter().enumerate() {
+                if next.peek() == Some(&&i) {
+                    next.next();
+                    a.push(v);
+                } else {
+                    b.push(v);
+                }
+            }
+            if diff(&mut a, &mut b).abs() >= threshold {
+                extreme += 1;
+            }
+
+            let Some(pos) = (0..n1).rev().find(|&i| idx[i] != i + n - n1) else {
+                break;
+            };
+            idx[pos] += 1;
+            for i in pos + 1..n1 {
+                idx[i] = idx[i - 1] + 1;
+            }
+        }
+        PermutationResult {
+            difference: observed,
+            p: extreme as f64 / arrangements as f64,
+            permutations: arrangements,
+            exact: true,
+        }
+    } else {
+        let mut shuffled = pooled.clone();
+        for _ in 0..permutations {
+            // A partial Fisher-Yates shuffle is enough to pick the first group
+            for i in 0..n1 {
+                let j = i + rng.below(n - i);
+                shuffled.swap(i, j);
+            }
+            a.clear();
+            a.extend_from_slice(&shuffled[..n1]);
+            b.clear();
+            b.extend_from_slice(&shuffled[n1..]);
+            if diff(&mut a, &mut b).abs() >= threshold {
+                extreme += 1;
+            }
+        }
+        PermutationResult {
+            difference: observed,
+            // Count the observed arrangement too, so p is never exactly 0
+            p: (extreme + 1) as f64 / (permutations + 1) as f64,
+            permutations,
+            exact: false,
+        }
+    }
+}
+
+pub fn print_permutation<W, T>(
+    f: &mut W,
+    data: &[T],
+    baseline: usize,
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+    T: AsRef<[f64]>,
+{
+    let confidence_label = T_CONFIDENCES[opt.confidence_level.0];
+    let alpha = 1. - opt.confidence_level.level();
+    let mut rng = Rng::new(opt.seed);
+    let others = others(symbols, data, baseline);
+    let baseline = data[baseline].as_ref();
+    for (&symbol, other) in others {
+        let result = permutation_test(
+            baseline,
+            other.as_ref(),
+            opt.permutation_statistic,
+            opt.permutations.get() as u64,
+            &mut rng,
+        );
+        // Differences in means get a parametric effect size, and medians a
+        // rank-based one
+        let effect = match opt.permutation_statistic {
+            Location::Mean => {
+                let g = hedges_g(
+                    &Stats::from_dataset(baseline),
+                    &Stats::from_dataset(other.as_ref()),
+                    opt.confidence_level.level(),
+                );
+                let line = format!(
+                    "Hedges' g = {:.6} [{:.6}, {:.6}]",
+                    g.estimate, g.low, g.high
+                );
+                (g.estimate, line)
+            }
+            Location::Median => {
+                let delta = cliffs_delta(baseline, other.as_ref());
+                (delta, format!("Cliff's delta = {:.6}", delta))
+            }
+        };
+        if result.p < alpha && effect.0.abs() < opt.min_effect {
+            writeln!(
+                f,
+                "{} No practical difference at {}% confidence",
+                symbol, confidence_label
+            )?;
+        } else if result.p < alpha {
+            writeln!(
+                f,
+                "{} Difference at {}% confidence",
+                symbol, confidence_label
+            )?;
+        } else {
+            writeln!(
+                f,
+                "{} No difference proven at {}% confidence",
+                symbol, confidence_label
+            )?;
+        }
+        if opt.log {
+            // A difference between logs is a ratio of the original data
+            writeln!(
+                f,
+                "\t{:.6}x ratio of {}s",
+                result.difference.exp(),
+                match opt.permutation_statistic {
+                    Location::Mean => "geometric mean",
+                    Location::Median => "median",
+                }
+            )?;
+        } else {
+            writeln!(
+                f,
+                "\t{:.6} difference in {}s",
+                result.difference,
+                match opt.permutation_statistic {
+                    Location::Mean => "mean",
+                    Location::Median => "median",
+                }
+            )?;
+        }
+        writeln!(
+            f,
+            "\t(Permutation p = {:.6}, {} over {} {})",
+            result.p,
+            if result.exact { "exact" } else { "sampled" },
+            result.permutations,
+            if result.exact {
+                "arrangements"
+            } else {
+                "random permutations"
+            }
+        )?;
+        writeln!(f, "\t{}", effect.1)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{choose, permutation_test};
+    use crate::{args::Location, rng::Rng};
+
+    #[test]
+    fn test_exact() {
+        assert_eq!(10, choose(5, 2));
+        assert_eq!(252, choose(10, 5));
+        // Completely separated groups of 5: only the observed split and its
+        // mirror image are as extreme
+        let a = [1., 2., 3., 4., 5.];
+        let b = [6., 7., 8., 9., 10.];
+        let mut rng = Rng::new(0);
+        let result = permutation_test(&a, &b, Location::Mean, 10000, &mut rng);
+        assert!(result.exact);
+        assert_eq!(252, result.permutations);
+        assert_eq!(5., result.difference);
+        assert!((result.p - 2. / 252.).abs() < 1e-12);
+
+        // Medians are coarser, so more splits tie with the observed one
+        let result = permutation_test(&a, &b, Location::Median, 10000, &mut rng);
+        assert_eq!(5., result.difference);
+        assert!((result.p - 12. / 252.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_sampled() {
+        let a = [1., 2., 3., 4., 5.];
+        let b = [6., 7., 8., 9., 10.];
+        let mut rng = Rng::new(0);
+        let result = permutation_test(&a, &b, Location::Mean, 100, &mut rng);
+        assert!(!result.exact);
+        assert_eq!(100, result.permutations);
+        assert!(result.p > 0. && result.p < 0.1);
+    }
+}
@@ -3,9 +3,9 @@ use noisy_float::prelude::*;
 use std::borrow::Borrow;
 use std::io::Write;
 use std::iter;
+use std::path::Path;
 
 use crate::args::Opt;
-use crate::data::Dataset;
 use crate::err::MinistatFailure;
 use crate::stats::Stats;
 
@@ -22,6 +22,9 @@ struct DrawingChars {
     bar_start: char,
     bar_end: char,
     bar: char,
+    outlier: char,
+    ci_start: char,
+    ci_end: char,
 }
 
 static CLASSIC_CHARS: DrawingChars = DrawingChars {
@@ -34,6 +37,9 @@ static CLASSIC_CHARS: DrawingChars = DrawingChars {
     bar_start: '|',
     bar_end: '|',
     bar: '_',
+    outlier: '!',
+    ci_start: '[',
+    ci_end: ']',
 };
 static MODERN_CHARS: DrawingChars = DrawingChars {
     ul: '┌',
@@ -45,8 +51,17 @@ static MODERN_CHARS: DrawingChars = DrawingChars {
     bar_start: '├',
     bar_end: '┤',
     bar: '─',
+    outlier: '✕',
+    ci_start: '⟦',
+    ci_end: '⟧',
 };
 
+/// Marks a plot cell holding an outlier, in place of a symbol index
+const OUTLIER: usize = usize::MAX;
+
+/// Height of the tallest column in a plot drawn from histograms
+const HISTOGRAM_ROWS: usize = 16;
+
 pub struct Plot {
     width: u16,
     max: f64,
@@ -54,11 +69,12 @@ pub struct Plot {
 }
 
 impl Plot {
-    pub fn new(width: u16, stats: &[Stats]) -> Result<Self, MinistatFailure> {
+    pub fn new(width: u16, stats: &[Stats], opt: &Opt) -> Result<Self, MinistatFailure> {
+        let spread = opt.spread();
         let max = stats
             .iter()
             .map(|stat| r64(stat.max))
-            .chain(stats.iter().map(|stat| r64(stat.mean + stat.stddev)))
+            .chain(stats.iter().map(|stat| r64(stat.bar(spread).1)))
             .max()
             .ok_or(MinistatFailure::NoPlotPossible)?
             .raw();
@@ -66,7 +82,7 @@ impl Plot {
         let min = stats
             .iter()
             .map(|stat| r64(stat.min))
-            .chain(stats.iter().map(|stat| r64(stat.mean - stat.stddev)))
+            .chain(stats.iter().map(|stat| r64(stat.bar(spread).0)))
             .min()
             .ok_or(MinistatFailure::NoPlotPossible)?
             .raw();
@@ -85,24 +101,20 @@ impl Plot {
         W: Write,
         T: Borrow<[f64]>,
     {
-        let drawing_chars = if opt.modern_chars {
-            &MODERN_CHARS
-        } else {
-            &CLASSIC_CHARS
-        };
         let col_count = (self.width - 2) as usize;
-        let mut columns: Vec<Vec<usize>> = iter::repeat(Vec::new()).take(col_count).collect();
-        let dx = (self.max - self.min) / ((col_count - 1) as f64);
-        let zero_point = self.min - 0.5 * dx;
-        let discretize = |pt: f64| ((pt - zero_point) / dx) as usize;
+        let mut columns: Vec<Vec<usize>> = iter::repeat_n(Vec::new(), col_count).collect();
+        let discretize = |pt: f64| self.column(pt);
 
         for (idx, dataset) in data.iter().enumerate() {
             let mut height = 0;
             let mut last_seen = None;
+            let fences = stats[idx].outliers.map(|o| o.fences);
             for datum in dataset.borrow().iter() {
                 let x = discretize(*datum);
+                let is_outlier = fences.is_some_and(|fences| !fences.contains(*datum));
+                let cell = if is_outlier { OUTLIER } else { idx + 1 };
                 if opt.stack {
-                    columns[x].push(idx + 1);
+                    columns[x].push(cell);
                 } else {
                     match last_seen {
                         Some(last) if last == x => {
@@ -114,14 +126,90 @@ impl Plot {
                         }
                     }
                     if columns[x].len() < height {
-                        columns[x].push(idx + 1);
-                    } else {
-                        columns[x][height - 1] |= idx + 1;
+                        columns[x].push(cell);
+                    } else if columns[x][height - 1] != OUTLIER {
+                        if is_outlier {
+                            columns[x][height - 1] = OUTLIER;
+                        } else {
+                            columns[x][height - 1] |= idx + 1;
+                        }
                     }
                 }
             }
         }
 
+        self.render(f, &columns, stats, symbols, opt)
+    }
+
+    /// Draws the same plot from a histogram of each dataset, given as the
+    /// centre and count of each bin. Each column is scaled to at most
+    /// `HISTOGRAM_ROWS` rows, with the datasets stacked.
+    pub fn draw_histograms<W>(
+        &self,
+        f: &mut W,
+        histograms: &[Vec<(f64, u64)>],
+        stats: &[Stats],
+        symbols: &[char],
+        opt: &Opt,
+    ) -> Result<(), Error>
+    where
+        W: Write,
+    {
+        let col_count = (self.width - 2) as usize;
+        let mut counts = vec![vec![0u64; col_count]; histograms.len()];
+        for (counts, histogram) in counts.iter_mut().zip(histograms) {
+            for &(centre, count) in histogram {
+                counts[self.column(centre).min(col_count - 1)] += count;
+            }
+        }
+        let tallest = (0..col_count)
+            .map(|col| counts.iter().map(|c| c[col]).sum::<u64>())
+            .max()
+            .unwrap_or(0)
+            .max(1) as f64;
+        let columns: Vec<Vec<usize>> = (0..col_count)
+            .map(|col| {
+                counts
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(idx, c)| {
+                        let rows = (c[col] as f64 / tallest * HISTOGRAM_ROWS as f64).ceil();
+                        iter::repeat_n(idx + 1, rows as usize)
+                    })
+                    .collect()
+            })
+            .collect();
+        self.render(f, &columns, stats, symbols, opt)
+    }
+
+    /// The plot column that a value falls in
+    fn column(&self, pt: f64) -> usize {
+        let col_count = (self.width - 2) as usize;
+        let dx = (self.max - self.min) / ((col_count - 1) as f64);
+        let zero_point = self.min - 0.5 * dx;
+        ((pt - zero_point) / dx) as usize
+    }
+
+    /// Draws the frame around columns of symbol indexes, and the bars
+    /// summarising each dataset below them
+    fn render<W>(
+        &self,
+        f: &mut W,
+        columns: &[Vec<usize>],
+        stats: &[Stats],
+        symbols: &[char],
+        opt: &Opt,
+    ) -> Result<(), Error>
+    where
+        W: Write,
+    {
+        let drawing_chars = if opt.modern_chars {
+            &MODERN_CHARS
+        } else {
+            &CLASSIC_CHARS
+        };
+        let col_count = columns.len();
+        let discretize = |pt: f64| self.column(pt);
         let max_height = columns.iter().map(|c| c.len()).max().unwrap();
 
         writeln!(
@@ -134,8 +222,10 @@ impl Plot {
 
         for row in (0..max_height).rev() {
             let mut row_text = String::new();
-            for col in &columns {
-                if col.len() > row {
+            for col in columns {
+                if col.len() > row && col[row] == OUTLIER {
+                    row_text.push(drawing_chars.outlier);
+                } else if col.len() > row {
                     row_text.push(symbols[col[row]]);
                 } else {
                     row_text.push(' ');
@@ -149,8 +239,9 @@ impl Plot {
             )?;
         }
         let draw_on_bar = |bar: &mut Vec<char>, stat: &Stats| {
-            let std_low = discretize(stat.mean - stat.stddev);
-            let std_high = discretize(stat.mean + stat.stddev);
+            let (low, high) = stat.bar(opt.spread());
+            let std_low = discretize(low);
+            let std_high = discretize(high);
             bar[std_low] = drawing_chars.bar_start;
             bar[std_high] = drawing_chars.bar_end;
             for bar_segment in bar.iter_mut().take(std_high).skip(std_low + 1) {
@@ -159,9 +250,13 @@ impl Plot {
                 }
             }
             bar[discretize(stat.mean)] = 'A';
+            if let Some((low, high)) = stat.median_ci {
+                bar[discretize(low)] = drawing_chars.ci_start;
+                bar[discretize(high)] = drawing_chars.ci_end;
+            }
             bar[discretize(stat.median)] = 'M';
         };
-        let make_bar = || iter::repeat(' ').take(col_count).collect::<Vec<_>>();
+        let make_bar = || iter::repeat_n(' ', col_count).collect::<Vec<_>>();
         if opt.separate_lines {
             for stat in stats.iter() {
                 let mut bar = make_bar();
@@ -211,17 +306,103 @@ where
     W: Write,
     T: Borrow<[f64]>,
 {
-    let plot = Plot::new(width, stats)?;
+    let plot = Plot::new(width, stats, opt)?;
     plot.draw(f, data, stats, char_set, opt)?;
     Ok(())
 }
 
-pub fn print_heading<W>(f: &mut W, sets: &[Dataset], symbols: &[char]) -> Result<(), Error>
+/// Draws a scatter plot of `points`, `height` rows tall, marking each with
+/// `symbol`, over the straight line through `line`'s intercept and slope
+pub fn plot_scatter<W>(
+    f: &mut W,
+    width: u16,
+    height: u16,
+    points: &[(f64, f64)],
+    line: Option<(f64, f64)>,
+    symbol: char,
+    opt: &Opt,
+) -> Result<(), Error>
+where
+    W: Write,
+{
+    let drawing_chars = if opt.modern_chars {
+        &MODERN_CHARS
+    } else {
+        &CLASSIC_CHARS
+    };
+    let range = |values: &mut dyn Iterator<Item = f64>| {
+        values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        })
+    };
+    let (x_min, x_max) = range(&mut points.iter().map(|p| p.0));
+    let (y_min, y_max) = range(&mut points.iter().map(|p| p.1));
+    if !(x_min < x_max && y_min < y_max) {
+        return Err(MinistatFailure::NoPlotPossible.into());
+    }
+    let col_count = (width - 2) as usize;
+    let row_count = height.max(2) as usize;
+    let dx = (x_max - x_min) / ((col_count - 1) as f64);
+    let dy = (y_max - y_min) / ((row_count - 1) as f64);
+    let cell = |value: f64, min: f64, step: f64| ((value - min) / step + 0.5).floor();
+
+    let mut rows = vec![vec![' '; col_count]; row_count];
+    if let Some((intercept, slope)) = line {
+        for (col, x) in (0..col_count).map(|col| (col, x_min + col as f64 * dx)) {
+            let row = cell(intercept + slope * x, y_min, dy);
+            if (0. ..row_count as f64).contains(&row) {
+                rows[row as usize][col] = drawing_chars.bar;
+            }
+        }
+    }
+    for &(x, y) in points {
+        rows[cell(y, y_min, dy) as usize][cell(x, x_min, dx) as usize] = symbol;
+    }
+
+    writeln!(
+        f,
+        "{}{}{}",
+        drawing_chars.ul,
+        drawing_chars.horiz.to_string().repeat(col_count),
+        drawing_chars.ur
+    )?;
+    for row in rows.iter().rev() {
+        writeln!(
+            f,
+            "{}{}{}",
+            drawing_chars.vert,
+            row.iter().collect::<String>(),
+            drawing_chars.vert
+        )?;
+    }
+    writeln!(
+        f,
+        "{}{}{}",
+        drawing_chars.ll,
+        drawing_chars.horiz.to_string().repeat(col_count),
+        drawing_chars.lr
+    )?;
+    Ok(())
+}
+
+/// Lists each dataset's symbol and path, marking `baseline` if given
+pub fn print_heading<W, T>(
+    f: &mut W,
+    sets: &[T],
+    baseline: Option<usize>,
+    symbols: &[char],
+) -> Result<(), Error>
 where
     W: Write,
+    T: AsRef<Path>,
 {
-    for (symbol, set) in symbols.iter().skip(1).zip(sets.iter()) {
-        writeln!(f, "{} {}", symbol, set.path.to_string_lossy())?;
+    for (idx, (symbol, set)) in symbols.iter().skip(1).zip(sets.iter()).enumerate() {
+        let path = set.as_ref().to_string_lossy();
+        if baseline == Some(idx) {
+            writeln!(f, "{} {} (baseline)", symbol, path)?;
+        } else {
+            writeln!(f, "{} {}", symbol, path)?;
+        }
     }
     Ok(())
 }
@@ -232,8 +413,9 @@ mod test {
 
     use super::Plot;
     use crate::{
-        args::Opt,
+        args::{Opt, OutlierMethod},
         data::Dataset,
+        outliers::detect,
         plot::{CLASSIC_SYMBOLS, UNICODE_SYMBOLS},
         print_heading,
         stats::Stats,
@@ -246,33 +428,40 @@ mod test {
             Dataset {
                 path: PathBuf::from_str("file1").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file2").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file3").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file4").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file5").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file6").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file7").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
         ];
-        print_heading(&mut buf, &datasets, &CLASSIC_SYMBOLS).unwrap();
+        print_heading(&mut buf, &datasets, None, &CLASSIC_SYMBOLS).unwrap();
         assert_eq!(
             "x file1\n+ file2\n* file3\n% file4\n# file5\n@ file6\nO file7\n",
             std::str::from_utf8(&buf).unwrap()
@@ -285,33 +474,40 @@ mod test {
             Dataset {
                 path: PathBuf::from_str("file1").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file2").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file3").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file4").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file5").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file6").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file7").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
         ];
-        print_heading(&mut buf, &datasets, &UNICODE_SYMBOLS).unwrap();
+        print_heading(&mut buf, &datasets, None, &UNICODE_SYMBOLS).unwrap();
         assert_eq!(
             "● file1\n○ file2\n◾ file3\n◽ file4\n◆ file5\n◇ file6\n▲ file7\n",
             std::str::from_utf8(&buf).unwrap()
@@ -327,37 +523,45 @@ mod test {
             Dataset {
                 path: PathBuf::from_str("file1").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file2").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file3").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file4").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file5").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file6").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file7").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
             Dataset {
                 path: PathBuf::from_str("file8").unwrap(),
                 data: vec![1.0, 2.0, 3.0, 4.0],
+                sequence: vec![1.0, 2.0, 3.0, 4.0],
             },
         ];
-        print_heading(&mut buf, &datasets, &CLASSIC_SYMBOLS).unwrap();
+        print_heading(&mut buf, &datasets, None, &CLASSIC_SYMBOLS).unwrap();
         assert_eq!(
             "x file1\n+ file2\n* file3\n% file4\n# file5\n@ file6\nO file7\n",
             std::str::from_utf8(&buf).unwrap()
@@ -370,14 +574,14 @@ mod test {
             vec![1., 2., 4., 8., 16.], // mean 6.2, median 4.0
             vec![5., 6., 7., 8., 9.],  // mean and median: 7.0
         ];
-        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(&*d)).collect();
-        let plot = Plot::new(30, &stats).unwrap();
+        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(d)).collect();
         let mut buf = Vec::new();
         let opt = Opt {
             separate_lines: true,
             stack: true,
             ..Opt::default()
         };
+        let plot = Plot::new(30, &stats, &opt).unwrap();
         plot.draw(&mut buf, &data, &stats, &CLASSIC_SYMBOLS, &opt)
             .unwrap();
         assert_eq!(
@@ -398,14 +602,14 @@ mod test {
             vec![1., 2., 4., 8., 16.], // mean 6.2, median 4.0
             vec![5., 6., 7., 8., 9.],  // mean and median: 7.0
         ];
-        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(&*d)).collect();
-        let plot = Plot::new(30, &stats).unwrap();
+        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(d)).collect();
         let mut buf = Vec::new();
         let opt = Opt {
             separate_lines: false,
             stack: false,
             ..Opt::default()
         };
+        let plot = Plot::new(30, &stats, &opt).unwrap();
         plot.draw(
             &mut buf,
             &data,
@@ -431,8 +635,7 @@ mod test {
             vec![1., 2., 4., 8., 16.], // mean 6.2, median 4.0
             vec![5., 6., 7., 8., 9.],  // mean and median: 7.0
         ];
-        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(&*d)).collect();
-        let plot = Plot::new(30, &stats).unwrap();
+        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(d)).collect();
         let mut buf = Vec::new();
         let opt = Opt {
             separate_lines: false,
@@ -440,6 +643,7 @@ mod test {
             modern_chars: true,
             ..Opt::default()
         };
+        let plot = Plot::new(30, &stats, &opt).unwrap();
         plot.draw(&mut buf, &data, &stats, &UNICODE_SYMBOLS, &opt)
             .unwrap();
         assert_eq!(
@@ -448,6 +652,32 @@ mod test {
 │  ●●   ●○ ○ ○◾ ○           ●│
 │├──────M─├A─M─┤──────┤      │
 └────────────────────────────┘
+",
+            std::str::from_utf8(&buf).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_plot_outliers() {
+        let data = [vec![1., 2., 3., 4., 5., 6., 7., 8., 9., 20.]];
+        let opt = Opt {
+            outliers: Some(OutlierMethod::Tukey),
+            ..Opt::default()
+        };
+        let stats: Vec<_> = data
+            .iter()
+            .map(|d| Stats::from_dataset(d).with_outliers(detect(d, &opt)))
+            .collect();
+        let mut buf = Vec::new();
+        let plot = Plot::new(30, &stats, &opt).unwrap();
+        plot.draw(&mut buf, &data, &stats, &CLASSIC_SYMBOLS, &opt)
+            .unwrap();
+        assert_eq!(
+            "\
++----------------------------+
+|xx xx xx xxx               !|
+||_____M_A______|            |
++----------------------------+
 ",
             std::str::from_utf8(&buf).unwrap()
         );
@@ -0,0 +1,148 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::{Margin, Opt};
+use crate::dist::{normal_quantile, t_quantile};
+use crate::stats::{welch, Stats};
+use crate::t_table::T_CONFIDENCES;
+
+/// Smallest difference in means that a two-sided Welch's t-test at
+/// confidence `level` detects with probability `power`, given the datasets'
+/// current sizes and variances
+pub fn minimum_detectable_effect(baseline: &Stats, other: &Stats, level: f64, power: f64) -> f64 {
+    let (val, dof) = welch(other, baseline);
+    (t_quantile(1. - (1. - level) / 2., dof) + t_quantile(power, dof)) * val.sqrt()
+}
+
+/// How many datapoints each of two datasets with the given variances needs
+/// for a two-sided Welch's t-test at confidence `level` to detect a
+/// difference of `effect` with probability `power`
+pub fn required_n(baseline: &Stats, other: &Stats, effect: f64, level: f64, power: f64) -> usize {
+    let var = baseline.var + other.var;
+    let n_for = |quantiles: f64| ((quantiles / effect).powi(2) * var).ceil().max(2.);
+    // Start from the normal approximation, then refine with the t
+    // distribution's degrees of freedom at that size
+    let mut n = n_for(normal_quantile(1. - (1. - level) / 2.) + normal_quantile(power));
+    for _ in 0..10 {
+        let dof = var.powi(2) * (n - 1.) / (baseline.var.powi(2) + other.var.powi(2));
+        let next = n_for(t_quantile(1. - (1. - level) / 2., dof) + t_quantile(power, dof));
+        if next == n {
+            break;
+        }
+        n = next;
+    }
+    n as usize
+}
+
+pub fn print_power<W>(
+    f: &mut W,
+    stats: &[Stats],
+    baseline: usize,
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+{
+    let level = opt.confidence_level.level();
+    let power = opt.power_level.0;
+    let baseline_stats = &stats[baseline];
+    for (idx, (&symbol, other)) in symbols.iter().skip(1).zip(stats).enumerate() {
+        if idx == baseline {
+            continue;
+        }
+        writeln!(
+            f,
+            "{} Power at {}% confidence with {}% power",
+            symbol,
+            T_CONFIDENCES[opt.confidence_level.0],
+            power * 100.
+        )?;
+        if baseline_stats.var <= 0. || other.var <= 0. {
+            writeln!(f, "\tUnavailable, as a dataset has no variance")?;
+            continue;
+        }
+        let mde = minimum_detectable_effect(baseline_stats, other, level, power);
+        if opt.log {
+            // A difference in the means of logs is a ratio of geometric means
+            writeln!(
+                f,
+                "\tMinimum detectable ratio {:.6}x ({:.6}%)",
+                mde.exp(),
+                mde.exp_m1() * 100.
+            )?;
+        } else {
+            writeln!(
+                f,
+                "\tMinimum detectable difference {:.6} ({:.6}%)",
+                mde,
+                mde / baseline_stats.mean.abs() * 100.
+            )?;
+        }
+        if let Some(target) = opt.target_effect {
+            let (effect, label) = match target {
+                Margin::Absolute(m) => (m, format!("{}", m)),
+                Margin::Relative(percent) if opt.log => {
+                    ((percent / 100.).ln_1p(), format!("{}%", percent))
+                }
+                Margin::Relative(percent) => (
+                    (percent / 100. * baseline_stats.mean).abs(),
+                    format!("{}%", percent),
+                ),
+            };
+            writeln!(
+                f,
+                "\t{} datapoints per dataset needed to detect a difference of {}",
+                required_n(baseline_stats, other, effect, level, power),
+                label
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{minimum_detectable_effect, print_power, required_n};
+    use crate::args::{Margin, Opt};
+    use crate::plot::CLASSIC_SYMBOLS;
+    use crate::stats::Stats;
+
+    #[test]
+    fn test_power() {
+        let a: Vec<f64> = (0..100).map(|x| (x % 10) as f64).collect();
+        let mut sorted = a.clone();
+        sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+        let stats = Stats::from_dataset(&sorted);
+
+        // Detecting the minimum detectable effect needs about the current n
+        let mde = minimum_detectable_effect(&stats, &stats, 0.95, 0.8);
+        let n = required_n(&stats, &stats, mde, 0.95, 0.8);
+        assert!((99..=101).contains(&n), "{}", n);
+
+        // The usual rule of thumb: 16 / d^2 per group at 95% and 80%
+        let d = 0.5;
+        let n = required_n(&stats, &stats, d * stats.stddev, 0.95, 0.8);
+        assert!((63..=65).contains(&n), "{}", n);
+
+        // Halving the effect quadruples the sample size
+        let n2 = required_n(&stats, &stats, d / 2. * stats.stddev, 0.95, 0.8);
+        assert!((4 * n - 4..=4 * n).contains(&n2), "{} {}", n, n2);
+    }
+
+    #[test]
+    fn test_no_variance() {
+        let stats = Stats::from_dataset(&[5., 5., 5., 5.]);
+        let opt = Opt {
+            target_effect: Some(Margin::Relative(1.)),
+            ..Opt::default()
+        };
+        let mut buf = vec![];
+        print_power(&mut buf, &[stats.clone(), stats], 0, &opt, &CLASSIC_SYMBOLS).unwrap();
+        assert_eq!(
+            "+ Power at 95% confidence with 80% power\n\tUnavailable, as a dataset has no variance\n",
+            std::str::from_utf8(&buf).unwrap()
+        );
+    }
+}
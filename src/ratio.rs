@@ -0,0 +1,192 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::{Opt, RatioCi};
+use crate::bootstrap::Interval;
+use crate::dist::t_quantile;
+use crate::stats::{welch, Stats};
+use crate::t_table::T_CONFIDENCES;
+
+/// Fieller's confidence interval at `level` for the ratio of `other`'s mean to
+/// the baseline's. There is no bounded interval when the baseline's mean is
+/// not significantly different from zero, in which case this returns `None`.
+pub fn fieller(baseline: &Stats, other: &Stats, level: f64) -> Option<Interval> {
+    let (_, dof) = welch(other, baseline);
+    let t = t_quantile(1. - (1. - level) / 2., dof);
+    let (m0, m1) = (baseline.mean, other.mean);
+    let (v0, v1) = (baseline.var / baseline.n as f64, other.var / other.n as f64);
+    // The interval is the set of ratios r for which m1 - r * m0 is not
+    // significantly different from zero, the roots of a quadratic in r
+    let a = m0 * m0 - t * t * v0;
+    if a <= 0. {
+        return None;
+    }
+    let b = m0 * m1;
+    let c = m1 * m1 - t * t * v1;
+    let root = (b * b - a * c).max(0.).sqrt();
+    Some(Interval {
+        estimate: m1 / m0,
+        low: (b - root) / a,
+        high: (b + root) / a,
+    })
+}
+
+/// Confidence interval at `level` for the ratio of `other`'s mean to the
+/// baseline's, using the delta method's approximation to its standard error
+pub fn delta_ratio(baseline: &Stats, other: &Stats, level: f64) -> Interval {
+    let (_, dof) = welch(other, baseline);
+    let t = t_quantile(1. - (1. - level) / 2., dof);
+    let ratio = other.mean / baseline.mean;
+    let se = ratio.abs()
+        * (other.var / other.n as f64 / other.mean.powi(2)
+            + baseline.var / baseline.n as f64 / baseline.mean.powi(2))
+        .sqrt();
+    Interval {
+        estimate: ratio,
+        low: ratio - t * se,
+        high: ratio + t * se,
+    }
+}
+
+/// Ratio of the geometric means of two datasets, with a confidence interval
+/// at `level` from Welch's t-test on the logs of the data. Returns `None` if
+/// either dataset has a non-positive value.
+pub fn geometric_ratio(baseline: &[f64], other: &[f64], level: f64) -> Option<Interval> {
+    if baseline.iter().chain(other).any(|&x| x <= 0.) {
+        return None;
+    }
+    let ln = |data: &[f64]| Stats::from_dataset(&data.iter().map(|x| x.ln()).collect::<Vec<_>>());
+    let (baseline, other) = (ln(baseline), ln(other));
+    let (val, dof) = welch(&other, &baseline);
+    let half_width = t_quantile(1. - (1. - level) / 2., dof) * val.sqrt();
+    let difference = other.mean - baseline.mean;
+    Some(Interval {
+        estimate: difference.exp(),
+        low: (difference - half_width).exp(),
+        high: (difference + half_width).exp(),
+    })
+}
+
+pub fn print_ratio<W, T>(
+    f: &mut W,
+    stats: &[Stats],
+    data: &[T],
+    baseline: usize,
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+    T: AsRef<[f64]>,
+{
+    // With --log the data are logs, but the ratios are of the original data
+    let raw: Option<(Vec<Vec<f64>>, Vec<Stats>)> = opt.log.then(|| {
+        let data: Vec<Vec<f64>> = data
+            .iter()
+            .map(|d| d.as_ref().iter().map(|x| x.exp()).collect())
+            .collect();
+        let stats = data.iter().map(|d| Stats::from_dataset(d)).collect();
+        (data, stats)
+    });
+    let (stats, data): (&[Stats], Vec<&[f64]>) = match &raw {
+        Some((data, stats)) => (stats, data.iter().map(|d| &d[..]).collect()),
+        None => (stats, data.iter().map(|d| d.as_ref()).collect()),
+    };
+    let confidence_label = T_CONFIDENCES[opt.confidence_level.0];
+    let level = opt.confidence_level.level();
+    let baseline_symbol = symbols[baseline + 1];
+    let baseline_data = data[baseline];
+    let baseline_stats = &stats[baseline];
+    for (idx, (&symbol, other)) in symbols.iter().skip(1).zip(stats).enumerate() {
+        if idx == baseline {
+            continue;
+        }
+        let other_data = data[idx];
+        let (ci, method) = match opt.ratio_ci {
+            RatioCi::Fieller => (fieller(baseline_stats, other, level), "Fieller"),
+            RatioCi::Delta => (
+                Some(delta_ratio(baseline_stats, other, level)),
+                "delta method",
+            ),
+        };
+        writeln!(
+            f,
+            "{} Mean ratio to {} at {}% confidence ({})",
+            symbol, baseline_symbol, confidence_label, method
+        )?;
+        match ci {
+            Some(ci) => writeln!(f, "\t{:.6}x [{:.6}, {:.6}]", ci.estimate, ci.low, ci.high)?,
+            None => writeln!(
+                f,
+                "\t{:.6}x, unbounded because {}'s mean is not significantly different from zero",
+                other.mean / baseline_stats.mean,
+                baseline_symbol
+            )?,
+        }
+        match geometric_ratio(baseline_data, other_data, level) {
+            Some(ci) => writeln!(
+                f,
+                "\tGeometric mean ratio {:.6}x [{:.6}, {:.6}]",
+                ci.estimate, ci.low, ci.high
+            )?,
+            None => writeln!(
+                f,
+                "\tGeometric mean ratio unavailable, as not every datapoint is positive"
+            )?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{delta_ratio, fieller, geometric_ratio, print_ratio};
+    use crate::args::Opt;
+    use crate::plot::CLASSIC_SYMBOLS;
+    use crate::stats::Stats;
+
+    #[test]
+    fn test_ratios() {
+        let a = [9., 10., 10., 10., 11.];
+        let b = [19., 20., 20., 20., 21.];
+        let (sa, sb) = (Stats::from_dataset(&a), Stats::from_dataset(&b));
+
+        let ci = fieller(&sa, &sb, 0.95).unwrap();
+        assert_eq!(2., ci.estimate);
+        // Fieller's interval is skewed upwards, unlike the delta method's
+        assert!(ci.low < 2. && ci.high - 2. > 2. - ci.low);
+        let delta = delta_ratio(&sa, &sb, 0.95);
+        assert!(((delta.high - 2.) - (2. - delta.low)).abs() < 1e-12);
+
+        // A baseline indistinguishable from zero has no bounded interval
+        let zero = Stats::from_dataset(&[-1., 0., 1.]);
+        assert!(fieller(&zero, &sb, 0.95).is_none());
+
+        let geo = geometric_ratio(&a, &b, 0.95).unwrap();
+        assert!((geo.estimate - 2.).abs() < 0.01);
+        assert!(geo.low < geo.estimate && geo.estimate < geo.high);
+        assert!(geometric_ratio(&[-1., 1., 2.], &b, 0.95).is_none());
+
+        // With --log the datasets hold logs, including negative ones for data
+        // below 1, but the ratios are still of the original data
+        let small = [0.09, 0.1, 0.1, 0.1, 0.11];
+        let print = |data: &[Vec<f64>], log: bool| {
+            let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(d)).collect();
+            let opt = Opt {
+                log,
+                ..Opt::default()
+            };
+            let mut buf = vec![];
+            print_ratio(&mut buf, &stats, data, 0, &opt, &CLASSIC_SYMBOLS).unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+        let logs: Vec<Vec<f64>> = [&small, &a]
+            .iter()
+            .map(|d| d.iter().map(|x: &f64| x.ln()).collect())
+            .collect();
+        let logged = print(&logs, true);
+        assert_eq!(print(&[small.to_vec(), a.to_vec()], false), logged);
+        assert!(logged.contains("\t100.000000x [") && logged.contains("ratio 100.000000x ["));
+    }
+}
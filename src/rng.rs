@@ -0,0 +1,69 @@
+/// xoshiro256**, seeded through SplitMix64. We only need something fast with
+/// good statistical quality that gives the same stream for the same seed on
+/// every platform, so that resampling results can be reproduced.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    s: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut sm = seed;
+        let mut next = || {
+            sm = sm.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = sm;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        Rng {
+            s: [next(), next(), next(), next()],
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+
+    /// A uniformly distributed integer in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        let n = n as u64;
+        // Reject the top partial range so that every value is equally likely
+        let zone = u64::MAX - (u64::MAX % n);
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return (x % n) as usize;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Rng;
+
+    #[test]
+    fn test_deterministic() {
+        let a: Vec<_> = {
+            let mut rng = Rng::new(42);
+            (0..10).map(|_| rng.below(100)).collect()
+        };
+        let b: Vec<_> = {
+            let mut rng = Rng::new(42);
+            (0..10).map(|_| rng.below(100)).collect()
+        };
+        assert_eq!(a, b);
+        assert!(a.iter().all(|&x| x < 100));
+        let mut rng = Rng::new(43);
+        assert_ne!(a, (0..10).map(|_| rng.below(100)).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Error;
+
+use crate::args::{Margin, Opt, SequentialOpt};
+use crate::err::MinistatFailure;
+use crate::stats::{welch, Stats};
+use crate::t_table::T_CONFIDENCES;
+
+/// The confidence sequence is tuned to be tightest after this many
+/// datapoints per dataset
+const TUNED_N: f64 = 100.;
+
+/// How long to wait before looking for more data with --follow
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Difference,
+    Equivalent,
+    Continue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sequence {
+    pub decision: Decision,
+    /// Difference in means, other minus baseline
+    pub difference: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+/// Half-width of an always-valid confidence sequence at significance
+/// `alpha` for a difference in means estimated with variance `var`, from a
+/// normal mixture over the alternatives (Robbins, 1970), with plug-in
+/// variances as in Waudby-Smith et al. (2021). With `n` datapoints per
+/// dataset, the mixture is tuned to be tightest at `TUNED_N` datapoints.
+pub fn half_width(var: f64, n: f64, alpha: f64) -> f64 {
+    // Two constant datasets have exactly the difference between them
+    if var <= 0. {
+        return 0.;
+    }
+    // The information, 1 / var, grows in proportion to n
+    let tuned_info = TUNED_N / (n * var);
+    let log_alpha = -2. * alpha.ln();
+    let mixture = (log_alpha + (log_alpha + 1.).ln()) / tuned_info;
+    let mixed = 1. + mixture / var;
+    (var * var * mixed / mixture * (log_alpha + mixed.ln())).sqrt()
+}
+
+/// Decides whether the samples so far show a difference in means, or
+/// equivalence within `margin`, at significance `alpha`, however many times
+/// this has been checked before
+pub fn decide(baseline: &Stats, other: &Stats, margin: Option<Margin>, alpha: f64) -> Sequence {
+    let (var, _) = welch(other, baseline);
+    let n = baseline.n.min(other.n) as f64;
+    let difference = other.mean - baseline.mean;
+    let half_width = half_width(var, n, alpha);
+    let (low, high) = (difference - half_width, difference + half_width);
+    let margin = margin.map(|margin| match margin {
+        Margin::Absolute(m) => m,
+        Margin::Relative(percent) => (percent / 100. * baseline.mean).abs(),
+    });
+    let decision = if low > 0. || high < 0. {
+        Decision::Difference
+    } else if margin.is_some_and(|m| -m < low && high < m) || var <= 0. {
+        // Without any variance, no difference means the same constant
+        Decision::Equivalent
+    } else {
+        Decision::Continue
+    };
+    Sequence {
+        decision,
+        difference,
+        low,
+        high,
+    }
+}
+
+/// Reads the complete lines from a source that may still be growing
+struct LineSource {
+    name: String,
+    reader: Box<dyn BufRead>,
+    partial: String,
+    line_no: usize,
+    /// Whether more data may be appended after reaching the end
+    follow: bool,
+}
+
+impl LineSource {
+    fn open(path: &Path, follow: bool) -> Result<Self, Error> {
+        Ok(LineSource {
+            name: path.to_string_lossy().into_owned(),
+            reader: Box::new(BufReader::new(File::open(path)?)),
+            partial: String::new(),
+            line_no: 0,
+            follow,
+        })
+    }
+
+    fn stdin() -> Self {
+        LineSource {
+            name: "stdin".into(),
+            reader: Box::new(BufReader::new(std::io::stdin())),
+            partial: String::new(),
+            line_no: 0,
+            follow: false,
+        }
+    }
+
+    /// The next complete line, if one is available yet. Unless more data may
+    /// be appended, a final line without a newline is complete too.
+    fn next_line(&mut self) -> Result<Option<String>, Error> {
+        let read = self.reader.read_line(&mut self.partial)?;
+        let complete =
+            self.partial.ends_with('\n') || (read == 0 && !self.follow && !self.partial.is_empty());
+        if !complete {
+            return Ok(None);
+        }
+        self.line_no += 1;
+        let line = std::mem::take(&mut self.partial);
+        Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+    }
+
+    fn invalid(&self) -> MinistatFailure {
+        MinistatFailure::InvalidData {
+            file: self.name.clone(),
+            line_no: self.line_no,
+        }
+    }
+}
+
+/// Parses the datapoint in `field` of `line`, if there is one
+fn parse_field(
+    source: &LineSource,
+    line: &str,
+    field: usize,
+    split_chars: &HashSet<char>,
+) -> Result<Option<f64>, Error> {
+    match line.split(|x| split_chars.contains(&x)).nth(field) {
+        Some(val) => {
+            let parsed = val.parse::<f64>().map_err(|_| source.invalid())?;
+            Ok(Some(parsed).filter(|x| x.is_finite()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Reads new datapoints into `data` until each has `until` of them. Returns
+/// whether that was cut short by a source reaching its end, for now.
+fn read_available(
+    sources: &mut [LineSource],
+    data: &mut [Vec<f64>; 2],
+    opt: &Opt,
+    split_chars: &HashSet<char>,
+    until: usize,
+) -> Result<bool, Error> {
+    let column = (opt.column.0 - 1) as usize;
+    if let [stdin] = sources {
+        // Each line names its dataset, then holds the datapoint
+        while data.iter().any(|d| d.len() < until) {
+            let line = match stdin.next_line()? {
+                Some(line) => line,
+                None => return Ok(true),
+            };
+            let dataset = match parse_field(stdin, &line, 0, split_chars)? {
+                Some(d) if d == 1. || d == 2. => d as usize - 1,
+                Some(_) => return Err(stdin.invalid().into()),
+                None => continue,
+            };
+            if let Some(x) = parse_field(stdin, &line, column + 1, split_chars)? {
+                data[dataset].push(x);
+            }
+        }
+        return Ok(false);
+    }
+    let mut cut_short = false;
+    for (source, data) in sources.iter_mut().zip(data.iter_mut()) {
+        while data.len() < until {
+            let line = match source.next_line()? {
+                Some(line) => line,
+                None => {
+                    cut_short = true;
+                    break;
+                }
+            };
+            if let Some(x) = parse_field(source, &line, column, split_chars)? {
+                data.push(x);
+            }
+        }
+    }
+    Ok(cut_short)
+}
+
+pub fn run_sequential(opt: &Opt, seq_opt: &SequentialOpt) -> Result<(), Error> {
+    let mut stdout = std::io::stdout().lock();
+    let mut sources = match &seq_opt.files[..] {
+        [] => vec![LineSource::stdin()],
+        [a, b] => vec![
+            LineSource::open(a, seq_opt.follow)?,
+            LineSource::open(b, seq_opt.follow)?,
+        ],
+        files => {
+            return Err(MinistatFailure::SequentialFiles {
+                file_count: files.len(),
+            }
+            .into())
+        }
+    };
+    let names: Vec<PathBuf> = match &seq_opt.files[..] {
+        [] => vec!["stdin (1)".into(), "stdin (2)".into()],
+        files => files.to_vec(),
+    };
+    let symbols = crate::get_symbols(opt);
+    for (symbol, name) in symbols.iter().skip(1).zip(&names) {
+        writeln!(stdout, "{} {}", symbol, name.to_string_lossy())?;
+    }
+
+    let split_chars: HashSet<char> = opt.delimiter.chars().collect();
+    let alpha = 1. - opt.confidence_level.level();
+    let batch = seq_opt.batch.get();
+    let mut data: [Vec<f64>; 2] = [vec![], vec![]];
+    let mut until = batch.max(3);
+    let mut reported = false;
+    loop {
+        let before = [data[0].len(), data[1].len()];
+        let exhausted = read_available(&mut sources, &mut data, opt, &split_chars, until)?;
+        // Standard input can't be followed once it ends
+        let follow = seq_opt.follow && !seq_opt.files.is_empty();
+        if exhausted && follow {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        // The data ended exactly at the last report, which covered all of it
+        if exhausted && reported && before == [data[0].len(), data[1].len()] {
+            return Ok(());
+        }
+        if data.iter().any(|d| d.len() < 3) {
+            writeln!(
+                stdout,
+                "n = {}, {}: not enough data, continue",
+                data[0].len(),
+                data[1].len()
+            )?;
+            return Ok(());
+        }
+        let stats: Vec<Stats> = data
+            .iter()
+            .map(|d| {
+                let mut sorted = d.clone();
+                sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+                Stats::from_dataset(&sorted)
+            })
+            .collect();
+        let result = decide(&stats[0], &stats[1], opt.equivalence, alpha);
+        writeln!(
+            stdout,
+            "n = {}, {}: {:.6} [{:.6}, {:.6}] at {}% confidence, {}",
+            data[0].len(),
+            data[1].len(),
+            result.difference,
+            result.low,
+            result.high,
+            T_CONFIDENCES[opt.confidence_level.0],
+            match result.decision {
+                Decision::Difference => "stop: difference",
+                Decision::Equivalent => "stop: equivalent",
+                Decision::Continue => "continue",
+            }
+        )?;
+        if result.decision != Decision::Continue || exhausted {
+            return Ok(());
+        }
+        reported = true;
+        until = data[0].len().min(data[1].len()) + batch;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decide, half_width, Decision};
+    use crate::{args::Margin, stats::Stats};
+
+    #[test]
+    fn test_confidence_sequence() {
+        // Wider than a fixed-n interval, which would use about 1.96 standard
+        // errors, to pay for being checked repeatedly
+        let var: f64 = 0.02;
+        let width = half_width(var, 100., 0.05);
+        assert!(width > 1.96 * var.sqrt() && width < 4. * var.sqrt());
+        // ...and still shrinking as data arrives
+        assert!(half_width(var / 2., 200., 0.05) < width);
+
+        let a: Vec<f64> = (0..100).map(|x| 100. + (x % 10) as f64).collect();
+        let b: Vec<f64> = (0..100).map(|x| 110. + (x % 10) as f64).collect();
+        let c: Vec<f64> = (0..100).map(|x| 100.1 + (x % 10) as f64).collect();
+        let (a, b, c) = (
+            Stats::from_dataset(&a),
+            Stats::from_dataset(&b),
+            Stats::from_dataset(&c),
+        );
+        assert_eq!(Decision::Difference, decide(&a, &b, None, 0.05).decision);
+        assert_eq!(Decision::Continue, decide(&a, &c, None, 0.05).decision);
+        assert_eq!(
+            Decision::Equivalent,
+            decide(&a, &c, Some(Margin::Relative(5.)), 0.05).decision
+        );
+
+        // Constant datasets differ exactly when their values do
+        let five = Stats::from_dataset(&[5., 5., 5., 5.]);
+        let six = Stats::from_dataset(&[6., 6., 6.]);
+        let same = decide(&five, &five, None, 0.05);
+        assert_eq!(Decision::Equivalent, same.decision);
+        assert_eq!((0., 0.), (same.low, same.high));
+        let different = decide(&five, &six, None, 0.05);
+        assert_eq!(Decision::Difference, different.decision);
+        assert_eq!((1., 1.), (different.low, different.high));
+    }
+}
@@ -2,6 +2,12 @@ use std::io::Write;
 
 use anyhow::Error;
 
+use crate::args::{Bandwidth, Opt, QuantileType, Spread, Test};
+use crate::autocorrelation::effective_n;
+use crate::effect::{cohens_d, hedges_g};
+use crate::kde::{kde, Kde};
+use crate::outliers::Outliers;
+
 #[derive(Debug, Clone)]
 pub struct Stats {
     pub n: usize,
@@ -11,6 +17,29 @@ pub struct Stats {
     pub stddev: f64,
     pub median: f64,
     pub mean: f64,
+    /// Median absolute deviation, scaled by 1.4826 (as R's `mad()` does) so
+    /// that it estimates the standard deviation of normal data
+    pub mad: f64,
+    pub q1: f64,
+    pub q3: f64,
+    pub iqr: f64,
+    pub trimmed_mean: f64,
+    pub winsorized_mean: f64,
+    /// Moment coefficient of skewness, m3 / m2^(3/2)
+    pub skewness: f64,
+    /// Excess kurtosis, m4 / m2^2 - 3, which is 0 for normal data
+    pub kurtosis: f64,
+    /// Requested percentiles (0-100), and their values
+    pub percentiles: Vec<(f64, f64)>,
+    /// Outliers found with `--outliers`, if requested
+    pub outliers: Option<Outliers>,
+    /// Distribution-free confidence interval for the median, if requested
+    pub median_ci: Option<(f64, f64)>,
+    /// Sample size allowing for autocorrelation, used in place of `n` by
+    /// Welch's t-test if requested
+    pub effective_n: Option<f64>,
+    /// Kernel density estimate, if requested
+    pub kde: Option<Kde>,
 }
 
 impl Stats {
@@ -45,6 +74,13 @@ impl Stats {
         } else {
             (data[data.len() / 2] + data[data.len() / 2 - 1]) / 2.
         };
+        let mut deviations: Vec<f64> = data.iter().map(|x| (x - median).abs()).collect();
+        deviations.sort_unstable_by(|a, b| a.total_cmp(b));
+        let mad = MAD_SCALE * quantile(&deviations, 0.5);
+        let q1 = quantile(data, 0.25);
+        let q3 = quantile(data, 0.75);
+        let moment = |k| data.iter().map(|x| (x - mean).powi(k)).sum::<f64>() / data.len() as f64;
+        let m2 = moment(2);
         Stats {
             max,
             min,
@@ -53,87 +89,410 @@ impl Stats {
             mean,
             var,
             n: data.len(),
+            mad,
+            q1,
+            q3,
+            iqr: q3 - q1,
+            trimmed_mean: trimmed_mean(data, DEFAULT_TRIM),
+            winsorized_mean: winsorized_mean(data, DEFAULT_TRIM),
+            skewness: moment(3) / m2.powf(1.5),
+            kurtosis: moment(4) / (m2 * m2) - 3.,
+            percentiles: vec![],
+            outliers: None,
+            median_ci: None,
+            effective_n: None,
+            kde: None,
+        }
+    }
+
+    /// Recomputes the trimmed and winsorised means of `data`, which must be
+    /// sorted, cutting `trim` (0 <= trim < 0.5) of the data from each end
+    pub fn with_trim(mut self, data: &[f64], trim: f64) -> Self {
+        self.trimmed_mean = trimmed_mean(data, trim);
+        self.winsorized_mean = winsorized_mean(data, trim);
+        self
+    }
+
+    pub fn with_outliers(mut self, outliers: Option<Outliers>) -> Self {
+        self.outliers = outliers;
+        self
+    }
+
+    /// Adds a confidence interval at `level`, if given, for the median of
+    /// `data`, which must be sorted
+    pub fn with_median_ci(mut self, data: &[f64], level: Option<f64>) -> Self {
+        self.median_ci = level.map(|level| quantile_ci(data, 0.5, level));
+        self
+    }
+
+    /// Adds the effective sample size of `sequence`, the data in the order it
+    /// was read, if `enabled`
+    pub fn with_effective_n(mut self, sequence: &[f64], enabled: bool) -> Self {
+        self.effective_n = enabled.then(|| effective_n(sequence));
+        self
+    }
+
+    /// Adds a kernel density estimate of `data`, which must be sorted, with the
+    /// given bandwidth rule if any
+    pub fn with_kde(mut self, data: &[f64], bandwidth: Option<Bandwidth>) -> Self {
+        self.kde = bandwidth.and_then(|bandwidth| kde(data, &self, bandwidth));
+        self
+    }
+
+    /// The sample size for Welch's t-test
+    pub fn welch_n(&self) -> f64 {
+        self.effective_n.unwrap_or(self.n as f64)
+    }
+
+    /// Coefficient of variation, the standard deviation relative to the mean.
+    /// For the logs of log-normal data, it is estimated from their variance.
+    pub fn cv(&self, log: bool) -> f64 {
+        if log {
+            self.var.exp_m1().sqrt()
+        } else {
+            self.stddev / self.mean
         }
     }
+
+    /// The ends of the bar drawn under the plot for this dataset
+    pub fn bar(&self, spread: Spread) -> (f64, f64) {
+        match spread {
+            Spread::Stddev => (self.mean - self.stddev, self.mean + self.stddev),
+            Spread::Mad => (self.median - self.mad, self.median + self.mad),
+            Spread::Iqr => (self.q1, self.q3),
+        }
+    }
+
+    /// Adds the given percentiles (0-100) of `data`, which must be sorted
+    pub fn with_percentiles(
+        mut self,
+        data: &[f64],
+        percentiles: &[f64],
+        qtype: QuantileType,
+    ) -> Self {
+        self.percentiles = percentiles
+            .iter()
+            .map(|&p| (p, quantile_with(data, p / 100., qtype)))
+            .collect();
+        self
+    }
+}
+
+/// Consistency constant making the MAD comparable to a standard deviation
+pub const MAD_SCALE: f64 = 1.4826;
+pub const DEFAULT_TRIM: f64 = 0.1;
+
+/// Mean of sorted data after discarding `trim` of it from each end
+pub fn trimmed_mean(sorted: &[f64], trim: f64) -> f64 {
+    let k = (sorted.len() as f64 * trim).floor() as usize;
+    let kept = &sorted[k..sorted.len() - k];
+    kept.iter().sum::<f64>() / kept.len() as f64
+}
+
+/// Mean of sorted data after clamping `trim` of it at each end to the most
+/// extreme value that remains
+pub fn winsorized_mean(sorted: &[f64], trim: f64) -> f64 {
+    let n = sorted.len();
+    let k = (n as f64 * trim).floor() as usize;
+    let kept: f64 = sorted[k..n - k].iter().sum();
+    (kept + k as f64 * (sorted[k] + sorted[n - 1 - k])) / n as f64
 }
 
+/// The `p`th quantile (0 <= p <= 1) of sorted data, interpolating linearly
+/// between order statistics. This matches R's default (type 7).
+pub fn quantile(sorted: &[f64], p: f64) -> f64 {
+    quantile_with(sorted, p, QuantileType(7))
+}
+
+/// The `p`th quantile (0 <= p <= 1) of sorted data, using one of the nine
+/// sample quantile definitions of Hyndman & Fan (1996), numbered as in R's
+/// `quantile()`.
+pub fn quantile_with(sorted: &[f64], p: f64, qtype: QuantileType) -> f64 {
+    quantile_by(sorted.len(), |i| sorted[i], p, qtype)
+}
+
+/// As `quantile_with`, but reading the `i`th smallest of `n` values through
+/// `get`, so that callers can skip or remap elements without copying.
+pub fn quantile_by<F>(n: usize, get: F, p: f64, qtype: QuantileType) -> f64
+where
+    F: Fn(usize) -> f64,
+{
+    // This follows the structure of R's implementation, including its fuzz
+    // factor for positions that land very close to an order statistic
+    let nf = n as f64;
+    let fuzz = 4. * f64::EPSILON;
+    let (j, h) = if qtype.0 <= 3 {
+        // Discontinuous definitions
+        let nppm = if qtype.0 == 3 { nf * p - 0.5 } else { nf * p };
+        let j = (nppm + fuzz).floor();
+        let h = match qtype.0 {
+            1 if nppm > j => 1.,
+            1 => 0.,
+            2 if nppm > j => 1.,
+            2 => 0.5,
+            _ if nppm != j || j % 2. == 1. => 1.,
+            _ => 0.,
+        };
+        (j, h)
+    } else {
+        // Continuous definitions, which differ in where they place the
+        // plotting positions of the order statistics
+        let (a, b) = match qtype.0 {
+            4 => (0., 1.),
+            5 => (0.5, 0.5),
+            6 => (0., 0.),
+            7 => (1., 1.),
+            8 => (1. / 3., 1. / 3.),
+            _ => (3. / 8., 3. / 8.),
+        };
+        let nppm = a + p * (nf + 1. - a - b);
+        let j = (nppm + fuzz).floor();
+        let h = nppm - j;
+        (j, if h.abs() < fuzz { 0. } else { h })
+    };
+    // Order statistics are numbered from 1, and clamped to the ends of the data
+    let x = |j: f64| get(j.clamp(1., nf) as usize - 1);
+    if h == 0. {
+        x(j)
+    } else if h == 1. {
+        x(j + 1.)
+    } else {
+        x(j) + h * (x(j + 1.) - x(j))
+    }
+}
+
+/// Distribution-free confidence interval at `level` for the `p`th quantile
+/// (0 < p < 1) of sorted data. The ends are order statistics chosen so that
+/// the number of points below the true quantile, which is binomial, lands
+/// between them with probability at least `level`. Small datasets can't reach
+/// that, and get the widest interval, from the minimum to the maximum.
+pub fn quantile_ci(sorted: &[f64], p: f64, level: f64) -> (f64, f64) {
+    use crate::dist::binomial_cdf;
+
+    let n = sorted.len();
+    let tail = (1. - level) / 2.;
+    let below = |k: usize| binomial_cdf(k as f64 - 1., n as f64, p);
+    // The lower end is the last order statistic with at most `tail`
+    // probability of being above the quantile, and the upper end the first
+    // with at most `tail` probability of being below it. These are
+    // numbered from 1.
+    let low = count_while(n, |k| below(k) <= tail).max(1);
+    let high = (count_while(n, |k| below(k) < 1. - tail) + 1).min(n);
+    (sorted[low - 1], sorted[high - 1])
+}
+
+/// How many of 1..=n satisfy `pred`, which must be true up to some point and
+/// false after it
+fn count_while<F>(n: usize, pred: F) -> usize
+where
+    F: Fn(usize) -> bool,
+{
+    let (mut lo, mut hi) = (0, n);
+    while lo < hi {
+        let mid = (lo + hi).div_ceil(2);
+        if pred(mid) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    lo
+}
+
+/// Pairs each dataset except the baseline with its symbol, for comparing
+/// against the baseline
+pub fn others<'a, T>(
+    symbols: &'a [char],
+    data: &'a [T],
+    baseline: usize,
+) -> impl Iterator<Item = (&'a char, &'a T)> {
+    symbols
+        .iter()
+        .skip(1)
+        .zip(data.iter())
+        .enumerate()
+        .filter(move |&(idx, _)| idx != baseline)
+        .map(|(_, pair)| pair)
+}
+
+/// The variance of the difference between the means of two datasets, and
+/// its Welch-Satterthwaite degrees of freedom
+pub fn welch(a: &Stats, b: &Stats) -> (f64, f64) {
+    let (na, nb) = (a.welch_n(), b.welch_n());
+    let val = a.var / na + b.var / nb;
+    let va = a.var.powi(2) / (na * na * (na - 1.));
+    let vb = b.var.powi(2) / (nb * nb * (nb - 1.));
+    (val, val.powi(2) / (va + vb))
+}
+
+/// Two-sided critical value of Student's t from `T_TABLE`, rounding the
+/// degrees of freedom down
+pub fn t_critical(dof: f64, confidence_idx: usize) -> f64 {
+    use crate::t_table::T_TABLE;
+
+    let v_floor = dof as usize;
+    if v_floor > 1000 {
+        T_TABLE[1000][confidence_idx]
+    } else {
+        T_TABLE[v_floor.max(1) - 1][confidence_idx]
+    }
+}
+
+/// Prints the table of statistics, comparing each dataset to the one at index
+/// `baseline`
 pub fn print_stats<W>(
     f: &mut W,
     stats: &[Stats],
-    confidence_idx: usize,
-    raw_stats: bool,
+    baseline: usize,
+    opt: &Opt,
     symbols: &[char],
 ) -> Result<(), Error>
 where
     W: Write,
 {
-    use crate::t_table::{T_CONFIDENCES, T_TABLE};
+    use crate::t_table::T_CONFIDENCES;
 
+    let confidence_idx = opt.confidence_level.0;
+    // Other tests report their comparisons separately
+    let raw_stats = opt.raw_stats || opt.test != Test::Welch;
+    let spread = opt.spread();
     let confidence_label = T_CONFIDENCES[confidence_idx];
     // This isn't necessary, but helps maintain symmetry between the header and data rows
     let symbol = ' ';
-    writeln!(
-        f,
-        "{symbol} {N:>3} {Min:>13} {Max:>13} {Median:>13} {Avg:>13} {Stddev:>13}",
-        symbol = symbol,
-        N = "N",
-        Min = "Min",
-        Max = "Max",
-        Median = "Median",
-        Avg = "Avg",
-        Stddev = "Stddev"
-    )?;
-    let mut first_stats = None;
-    let fmt_decimal = |x| {
+    if spread == Spread::Stddev {
+        write!(
+            f,
+            "{symbol} {N:>3} {Min:>13} {Max:>13} {Median:>13} {Avg:>13} {Stddev:>13}",
+            symbol = symbol,
+            N = "N",
+            Min = "Min",
+            Max = "Max",
+            Median = "Median",
+            Avg = if opt.log { "Geo mean" } else { "Avg" },
+            Stddev = if opt.log { "Geo stddev" } else { "Stddev" }
+        )?;
+    } else {
+        write!(
+            f,
+            "{symbol} {N:>3} {Min:>13} {Max:>13} {Median:>13} {Spread:>13} {Trimmed:>13} \
+                  {Winsorized:>13}",
+            symbol = symbol,
+            N = "N",
+            Min = "Min",
+            Max = "Max",
+            Median = "Median",
+            Spread = if spread == Spread::Mad { "MAD" } else { "IQR" },
+            Trimmed = "Trimmed",
+            Winsorized = "Winsorized"
+        )?;
+    }
+    if opt.test_variance {
+        write!(f, " {:>13}", "CV")?;
+    }
+    if let Some(first) = stats.first() {
+        for (p, _) in &first.percentiles {
+            write!(f, " {:>13}", format!("p{}", p))?;
+        }
+        if first.median_ci.is_some() {
+            write!(f, " {:>13} {:>13}", "Median low", "Median high")?;
+        }
+        if first.outliers.is_some() {
+            write!(f, " {:>8}", "Outliers")?;
+        }
+    }
+    writeln!(f)?;
+    // With --log, the stats describe the logs of the data, which are shown
+    // back on the original scale: the mean and standard deviation become the
+    // geometric mean and geometric standard deviation factor
+    let fmt_decimal = |x: f64| {
+        let x = if opt.log { x.exp() } else { x };
         format!("{:13.6}", x)
             .trim_start_matches('0')
             .trim_start_matches('.')
             .to_string()
     };
-    for (&symbol, stats) in symbols.iter().skip(1).zip(stats.iter()) {
-        writeln!(
-            f,
-            "{symbol} {N:>3} {Min:>13} {Max:>13} {Median:>13} {Avg:>13} \
-                  {Stddev:>13}",
-            symbol = symbol,
-            N = stats.n,
-            Min = fmt_decimal(stats.min),
-            Max = fmt_decimal(stats.max),
-            Median = fmt_decimal(stats.median),
-            Avg = fmt_decimal(stats.mean),
-            Stddev = fmt_decimal(stats.stddev)
-        )?;
-        if !raw_stats && first_stats.is_none() {
-            first_stats = Some(stats.clone());
-        } else if let Some(ref fs) = first_stats {
-            let val = stats.var / (stats.n as f64) + fs.var / (fs.n as f64);
+    let baseline_stats = &stats[baseline];
+    for (idx, (&symbol, stats)) in symbols.iter().skip(1).zip(stats.iter()).enumerate() {
+        if spread == Spread::Stddev {
+            write!(
+                f,
+                "{symbol} {N:>3} {Min:>13} {Max:>13} {Median:>13} {Avg:>13} \
+                      {Stddev:>13}",
+                symbol = symbol,
+                N = stats.n,
+                Min = fmt_decimal(stats.min),
+                Max = fmt_decimal(stats.max),
+                Median = fmt_decimal(stats.median),
+                Avg = fmt_decimal(stats.mean),
+                Stddev = fmt_decimal(stats.stddev)
+            )?;
+        } else {
+            write!(
+                f,
+                "{symbol} {N:>3} {Min:>13} {Max:>13} {Median:>13} {Spread:>13} \
+                      {Trimmed:>13} {Winsorized:>13}",
+                symbol = symbol,
+                N = stats.n,
+                Min = fmt_decimal(stats.min),
+                Max = fmt_decimal(stats.max),
+                Median = fmt_decimal(stats.median),
+                Spread = fmt_decimal(if spread == Spread::Mad {
+                    stats.mad
+                } else {
+                    stats.iqr
+                }),
+                Trimmed = fmt_decimal(stats.trimmed_mean),
+                Winsorized = fmt_decimal(stats.winsorized_mean)
+            )?;
+        }
+        if opt.test_variance {
+            write!(f, " {:13.6}", stats.cv(opt.log))?;
+        }
+        for (_, value) in &stats.percentiles {
+            write!(f, " {:>13}", fmt_decimal(*value))?;
+        }
+        if let Some((low, high)) = stats.median_ci {
+            write!(f, " {:>13} {:>13}", fmt_decimal(low), fmt_decimal(high))?;
+        }
+        if let Some(outliers) = stats.outliers {
+            write!(f, " {:>8}", outliers.count)?;
+        }
+        writeln!(f)?;
+        if !raw_stats && idx != baseline {
+            let fs = baseline_stats;
             // Because we the sample sizes and variances might differ, we
             // use https://en.wikipedia.org/wiki/Welch%27s_t-test
             // to compute a t value.
+            let (val, v) = welch(stats, fs);
             let t = ((stats.mean - fs.mean) / val.sqrt()).abs();
-            let a = stats.var.powi(2) / (stats.n * stats.n * (stats.n - 1)) as f64;
-            let b = fs.var.powi(2) / (fs.n * fs.n * (fs.n - 1)) as f64;
-            let v = val.powi(2) / (a + b);
-
-            let v_floor = v as usize;
-            let t_required = if v_floor > 1000 {
-                T_TABLE[1000][confidence_idx]
-            } else {
-                T_TABLE[v_floor - 1][confidence_idx]
-            };
-            if t > t_required {
+            let t_required = t_critical(v, confidence_idx);
+            let half_width = t_required * val.sqrt();
+            let g = hedges_g(fs, stats, opt.confidence_level.level());
+            if t > t_required && g.estimate.abs() < opt.min_effect {
+                writeln!(
+                    f,
+                    "No practical difference at {}% confidence",
+                    confidence_label
+                )?;
+            } else if t > t_required && opt.log {
+                let difference = stats.mean - fs.mean;
                 writeln!(f, "Difference at {}% confidence", confidence_label)?;
                 writeln!(
                     f,
-                    "\t{:.6} +/- {:.6}",
-                    stats.mean - fs.mean,
-                    t_required * val
+                    "\t{:.6}x [{:.6}, {:.6}] ratio of geometric means",
+                    difference.exp(),
+                    (difference - half_width).exp(),
+                    (difference + half_width).exp()
                 )?;
+                writeln!(f, "\t(Welch's t = {:.6} on logs)", t)?;
+            } else if t > t_required {
+                writeln!(f, "Difference at {}% confidence", confidence_label)?;
+                writeln!(f, "\t{:.6} +/- {:.6}", stats.mean - fs.mean, half_width)?;
                 writeln!(
                     f,
                     "\t{:.6}% +/- {:.6}%",
                     (stats.mean - fs.mean) / fs.mean * 100.,
-                    t_required * val * 100. / fs.mean
+                    half_width * 100. / fs.mean
                 )?;
                 writeln!(f, "\t(Welch's t = {:.6})", t)?;
             } else {
@@ -143,6 +502,14 @@ where
                     confidence_label
                 )?;
             }
+            writeln!(
+                f,
+                "\tHedges' g = {:.6} [{:.6}, {:.6}] (Cohen's d = {:.6})",
+                g.estimate,
+                g.low,
+                g.high,
+                cohens_d(fs, stats)
+            )?;
         }
     }
     Ok(())
@@ -152,23 +519,25 @@ where
 mod test {
     use crate::plot::CLASSIC_SYMBOLS;
 
-    use super::{print_stats, Stats};
+    use super::{print_stats, quantile_ci, quantile_with, Stats};
+    use crate::args::{Opt, QuantileType};
 
     #[test]
     fn test_stats() {
-        let data = vec![
+        let data = [
             vec![1., 2., 4., 8., 16.], // mean 6.2, median 4.0
             vec![5., 6., 7., 8., 9.],  // mean and median: 7.0
         ];
-        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(&*d)).collect();
+        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(d)).collect();
         let mut buf = vec![];
-        print_stats(&mut buf, &stats, 2, false, &CLASSIC_SYMBOLS).unwrap();
+        print_stats(&mut buf, &stats, 0, &Opt::default(), &CLASSIC_SYMBOLS).unwrap();
         let s = std::str::from_utf8(&buf).unwrap();
         assert_eq!(
             "    N           Min           Max        Median           Avg        Stddev
 x   5      1.000000     16.000000      4.000000      6.200000      6.099180
 +   5      5.000000      9.000000      7.000000      7.000000      1.581139
 No difference proven at 95% confidence
+\tHedges' g = 0.162183 [-1.079443, 1.403810] (Cohen's d = 0.179560)
 ",
             s
         );
@@ -176,24 +545,156 @@ No difference proven at 95% confidence
 
     #[test]
     fn test_stats2() {
-        let data = vec![
+        let data = [
             vec![1., 2., 4., 8., 16.],     // mean 6.2, median 4.0
             vec![15., 16., 17., 18., 19.], // mean and median: 17.0
         ];
-        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(&*d)).collect();
+        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(d)).collect();
         let mut buf = vec![];
-        print_stats(&mut buf, &stats, 2, false, &CLASSIC_SYMBOLS).unwrap();
+        print_stats(&mut buf, &stats, 0, &Opt::default(), &CLASSIC_SYMBOLS).unwrap();
         let s = std::str::from_utf8(&buf).unwrap();
         assert_eq!(
             "    N           Min           Max        Median           Avg        Stddev
 x   5      1.000000     16.000000      4.000000      6.200000      6.099180
 +   5     15.000000     19.000000     17.000000     17.000000      1.581139
 Difference at 95% confidence
-\t10.800000 +/- 22.045013
-\t174.193548% +/- 355.564726%
+\t10.800000 +/- 7.823482
+\t174.193548% +/- 126.185199%
+\t(Welch's t = 3.832777)
+\tHedges' g = 2.189474 [0.621883, 3.757065] (Cohen's d = 2.424061)
+",
+            s
+        );
+    }
+
+    #[test]
+    fn test_stats_baseline() {
+        let data = [
+            vec![1., 2., 4., 8., 16.],     // mean 6.2, median 4.0
+            vec![15., 16., 17., 18., 19.], // mean and median: 17.0
+        ];
+        let stats: Vec<_> = data.iter().map(|d| Stats::from_dataset(d)).collect();
+        let mut buf = vec![];
+        print_stats(&mut buf, &stats, 1, &Opt::default(), &CLASSIC_SYMBOLS).unwrap();
+        let s = std::str::from_utf8(&buf).unwrap();
+        assert_eq!(
+            "    N           Min           Max        Median           Avg        Stddev
+x   5      1.000000     16.000000      4.000000      6.200000      6.099180
+Difference at 95% confidence
+\t-10.800000 +/- 7.823482
+\t-63.529412% +/- 46.020485%
 \t(Welch's t = 3.832777)
+\tHedges' g = -2.189474 [-3.757065, -0.621883] (Cohen's d = -2.424061)
++   5     15.000000     19.000000     17.000000     17.000000      1.581139
+",
+            s
+        );
+    }
+
+    #[test]
+    fn test_stats_log() {
+        let data: [Vec<f64>; 2] = [vec![1., 2., 4., 8.], vec![8., 16., 32., 64.]];
+        let stats: Vec<_> = data
+            .iter()
+            .map(|d| Stats::from_dataset(&d.iter().map(|x| x.ln()).collect::<Vec<_>>()))
+            .collect();
+        let opt = Opt {
+            log: true,
+            ..Opt::default()
+        };
+        let mut buf = vec![];
+        print_stats(&mut buf, &stats, 0, &opt, &CLASSIC_SYMBOLS).unwrap();
+        let s = std::str::from_utf8(&buf).unwrap();
+        let lines: Vec<_> = s.lines().collect();
+        assert!(lines[0].ends_with("Geo mean    Geo stddev"));
+        // Geometric means of 2^1.5 and 2^4.5, each with a geometric stddev
+        // factor of 2^sqrt(5/3)
+        assert_eq!(
+            "x   4      1.000000      8.000000      2.828427      2.828427      2.446967",
+            lines[1]
+        );
+        assert_eq!("Difference at 95% confidence", lines[3]);
+        assert!(lines[4].starts_with("\t8.000000x ["));
+    }
+
+    #[test]
+    fn test_quantile_ci() {
+        let data: Vec<f64> = (1..=40).map(|x| x as f64).collect();
+        // The usual table values: the 14th and 27th of 40 points
+        assert_eq!((14., 27.), quantile_ci(&data, 0.5, 0.95));
+        assert_eq!((1., 5.), quantile_ci(&data[..5], 0.5, 0.95));
+        let (low, high) = quantile_ci(&data, 0.9, 0.95);
+        assert!(low < 36. && 36. < high);
+    }
+
+    #[test]
+    fn test_quantile_types() {
+        let data = [1., 2., 4., 8., 16.];
+        // Values from R's quantile(c(1, 2, 4, 8, 16), 0.9, type = t)
+        let expected = [16., 16., 8., 12., 16., 16., 12.8, 16., 16.];
+        for (t, expected) in (1..=9).zip(expected) {
+            let q = quantile_with(&data, 0.9, QuantileType(t));
+            assert!((q - expected).abs() < 1e-12, "type {}: {}", t, q);
+        }
+        for t in 1..=9 {
+            assert_eq!(1., quantile_with(&data, 0., QuantileType(t)));
+            assert_eq!(16., quantile_with(&data, 1., QuantileType(t)));
+        }
+    }
+
+    #[test]
+    fn test_stats_percentiles() {
+        let data = [[1., 2., 4., 8., 16.], [5., 6., 7., 8., 9.]];
+        let stats: Vec<_> = data
+            .iter()
+            .map(|d| Stats::from_dataset(d).with_percentiles(d, &[90., 99.9], QuantileType(7)))
+            .collect();
+        let mut buf = vec![];
+        let opt = Opt {
+            raw_stats: true,
+            ..Opt::default()
+        };
+        print_stats(&mut buf, &stats, 0, &opt, &CLASSIC_SYMBOLS).unwrap();
+        let s = std::str::from_utf8(&buf).unwrap();
+        assert_eq!(
+            "    N           Min           Max        Median           Avg        Stddev           p90         p99.9
+x   5      1.000000     16.000000      4.000000      6.200000      6.099180     12.800000     15.968000
++   5      5.000000      9.000000      7.000000      7.000000      1.581139      8.600000      8.996000
 ",
             s
         );
     }
+
+    #[test]
+    fn test_robust_stats() {
+        let data = [1., 2., 3., 4., 5., 6., 7., 8., 9., 100.];
+        let stats = Stats::from_dataset(&data);
+        assert_eq!(5.5, stats.median);
+        // |x - 5.5| has median 2.5
+        assert!((stats.mad - 2.5 * 1.4826).abs() < 1e-12);
+        assert_eq!(3.25, stats.q1);
+        assert_eq!(7.75, stats.q3);
+        assert_eq!(4.5, stats.iqr);
+        // Trimming 10% drops 1 and 100
+        assert_eq!(5.5, stats.trimmed_mean);
+        // Winsorising 10% replaces them with 2 and 9
+        assert_eq!(5.5, stats.winsorized_mean);
+        let stats = stats.with_trim(&data, 0.);
+        assert_eq!(14.5, stats.trimmed_mean);
+        assert_eq!(14.5, stats.winsorized_mean);
+
+        let mut buf = vec![];
+        let opt = Opt {
+            raw_stats: true,
+            robust: true,
+            ..Opt::default()
+        };
+        print_stats(&mut buf, &[stats], 0, &opt, &CLASSIC_SYMBOLS).unwrap();
+        assert_eq!(
+            "    N           Min           Max        Median           MAD       Trimmed    Winsorized
+x  10      1.000000    100.000000      5.500000      3.706500     14.500000     14.500000
+",
+            std::str::from_utf8(&buf).unwrap()
+        );
+    }
 }
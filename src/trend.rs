@@ -0,0 +1,299 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::args::Opt;
+use crate::bootstrap::Interval;
+use crate::dist::{normal_cdf, normal_quantile};
+use crate::stats::quantile;
+
+/// Sen's slope considers every pair of datapoints, so longer sequences are
+/// thinned to about this many evenly spaced datapoints first
+const SEN_MAX_N: usize = 2000;
+
+/// Shortest segment between change points
+const MIN_SEGMENT: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MannKendall {
+    /// Later datapoints above earlier ones, minus those below them
+    pub s: f64,
+    /// Variance of `s` under no trend, corrected for ties
+    pub var: f64,
+    pub z: f64,
+    pub p: f64,
+}
+
+/// The size of each group of tied values in sorted data
+fn tie_sizes(sorted: &[f64]) -> Vec<f64> {
+    let mut sizes = vec![];
+    let mut start = 0;
+    while start < sorted.len() {
+        let t = sorted[start..]
+            .iter()
+            .take_while(|&&x| x == sorted[start])
+            .count();
+        sizes.push(t as f64);
+        start += t;
+    }
+    sizes
+}
+
+/// Sorts `values`, returning how many pairs were out of order, by merge sort
+fn count_inversions(values: &mut [f64]) -> u64 {
+    if values.len() < 2 {
+        return 0;
+    }
+    let mid = values.len() / 2;
+    let mut inversions =
+        count_inversions(&mut values[..mid]) + count_inversions(&mut values[mid..]);
+    let mut merged = Vec::with_capacity(values.len());
+    let (mut i, mut j) = (0, mid);
+    while i < mid && j < values.len() {
+        if values[j] < values[i] {
+            // Every remaining value on the left is above this one
+            inversions += (mid - i) as u64;
+            merged.push(values[j]);
+            j += 1;
+        } else {
+            merged.push(values[i]);
+            i += 1;
+        }
+    }
+    merged.extend_from_slice(&values[i..mid]);
+    merged.extend_from_slice(&values[j..]);
+    values.copy_from_slice(&merged);
+    inversions
+}
+
+/// The Mann-Kendall test for a monotonic trend over a sequence, counting
+/// its pairs in O(n log n) time as in Knight's (1966) algorithm for Kendall's
+/// tau
+pub fn mann_kendall(sequence: &[f64]) -> MannKendall {
+    let n = sequence.len() as f64;
+    let mut sorted = sequence.to_vec();
+    let discordant = count_inversions(&mut sorted) as f64;
+    let ties = tie_sizes(&sorted);
+    // Each group of t ties accounts for t(t - 1) / 2 pairs that are neither
+    // concordant nor discordant, and reduces the variance
+    let tied: f64 = ties.iter().map(|t| t * (t - 1.) / 2.).sum();
+    let tie_correction: f64 = ties.iter().map(|t| t * (t - 1.) * (2. * t + 5.)).sum();
+    let concordant = n * (n - 1.) / 2. - tied - discordant;
+    let s = concordant - discordant;
+    let var = (n * (n - 1.) * (2. * n + 5.) - tie_correction) / 18.;
+    // With a continuity correction
+    let z = if var > 0. {
+        s.signum() * (s.abs() - 1.).max(0.) / var.sqrt()
+    } else {
+        0.
+    };
+    MannKendall {
+        s,
+        var,
+        z,
+        p: 2. * (1. - normal_cdf(z.abs())),
+    }
+}
+
+/// Sen's slope, the median slope between pairs of datapoints in a sequence,
+/// per datapoint, with Gilbert's (1987) confidence interval at `level` from
+/// the order statistics of those slopes
+pub fn sens_slope(sequence: &[f64], level: f64) -> Interval {
+    let stride = sequence.len().div_ceil(SEN_MAX_N);
+    let thinned: Vec<f64> = sequence.iter().step_by(stride).copied().collect();
+    let mut slopes = Vec::with_capacity(thinned.len() * (thinned.len() - 1) / 2);
+    for (i, a) in thinned.iter().enumerate() {
+        for (j, b) in thinned.iter().enumerate().skip(i + 1) {
+            slopes.push((b - a) / ((j - i) * stride) as f64);
+        }
+    }
+    slopes.sort_unstable_by(|a, b| a.total_cmp(b));
+    let count = slopes.len() as f64;
+    let c = normal_quantile(1. - (1. - level) / 2.) * mann_kendall(&thinned).var.sqrt();
+    // The ends are the (count - c) / 2th and (count + c) / 2 + 1th slopes,
+    // numbered from 1
+    let rank = |r: f64| slopes[(r.round() as usize).clamp(1, slopes.len()) - 1];
+    Interval {
+        estimate: quantile(&slopes, 0.5),
+        low: rank((count - c) / 2.),
+        high: rank((count + c) / 2. + 1.),
+    }
+}
+
+/// Positions at which the mean of a sequence shifts, found with PELT (Killick
+/// et al., 2012) minimising the squared error within segments, scaled by a
+/// robust estimate of the noise variance, plus a penalty of 3 ln n per
+/// change point (as for MBIC). Each position is the index of the first
+/// datapoint after the change.
+pub fn change_points(sequence: &[f64]) -> Vec<usize> {
+    let n = sequence.len();
+    if n < 2 * MIN_SEGMENT {
+        return vec![];
+    }
+    // The noise's standard deviation, from the median absolute difference
+    // between neighbours, which a shift in the mean barely affects
+    let mut differences: Vec<f64> = sequence.windows(2).map(|w| (w[1] - w[0]).abs()).collect();
+    differences.sort_unstable_by(|a, b| a.total_cmp(b));
+    let sigma = quantile(&differences, 0.5) / (0.6745 * 2f64.sqrt());
+    if sigma <= 0. {
+        return vec![];
+    }
+    // Prefix sums of the standardised data, for the cost of any segment
+    let mean = sequence.iter().sum::<f64>() / n as f64;
+    let mut sums = vec![(0., 0.); n + 1];
+    for (i, x) in sequence.iter().enumerate() {
+        let z = (x - mean) / sigma;
+        sums[i + 1] = (sums[i].0 + z, sums[i].1 + z * z);
+    }
+    let cost = |s: usize, t: usize| {
+        let (sum, sum_sq) = (sums[t].0 - sums[s].0, sums[t].1 - sums[s].1);
+        sum_sq - sum * sum / (t - s) as f64
+    };
+
+    let penalty = 3. * (n as f64).ln();
+    // The best total cost of the first t datapoints, and where its last
+    // segment starts
+    let mut best = vec![f64::INFINITY; n + 1];
+    let mut last = vec![0; n + 1];
+    best[0] = -penalty;
+    let mut candidates: Vec<usize> = vec![];
+    for t in MIN_SEGMENT..=n {
+        let newest = t - MIN_SEGMENT;
+        if newest == 0 || newest >= MIN_SEGMENT {
+            candidates.push(newest);
+        }
+        let (start, total) = candidates
+            .iter()
+            .map(|&s| (s, best[s] + cost(s, t) + penalty))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        best[t] = total;
+        last[t] = start;
+        // A start that is already worse than the best, before paying for
+        // another change, can never become the best
+        candidates.retain(|&s| best[s] + cost(s, t) <= total);
+    }
+
+    let mut changes = vec![];
+    let mut t = n;
+    while last[t] > 0 {
+        t = last[t];
+        changes.push(t);
+    }
+    changes.reverse();
+    changes
+}
+
+pub fn print_trend<W, T>(
+    f: &mut W,
+    sequences: &[T],
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+    T: AsRef<[f64]>,
+{
+    let alpha = 1. - opt.confidence_level.level();
+    // With --log, slopes and means are of logs, so show them as factors and
+    // geometric means
+    let show = |x: f64| if opt.log { x.exp() } else { x };
+    let per = if opt.log { "x" } else { "" };
+    for (&symbol, sequence) in symbols.iter().skip(1).zip(sequences) {
+        let sequence = sequence.as_ref();
+        writeln!(f, "{} Trend over sample order", symbol)?;
+        let result = mann_kendall(sequence);
+        let trending = result.p < alpha;
+        writeln!(
+            f,
+            "\tMann-Kendall S = {}, Z = {:.6}, p = {:.6}: {}",
+            result.s,
+            result.z,
+            result.p,
+            if !trending {
+                "no trend proven"
+            } else if result.s > 0. {
+                "increasing"
+            } else {
+                "decreasing"
+            }
+        )?;
+        let slope = sens_slope(sequence, opt.confidence_level.level());
+        writeln!(
+            f,
+            "\tSen's slope {:.6}{} [{:.6}{}, {:.6}{}] per datapoint",
+            show(slope.estimate),
+            per,
+            show(slope.low),
+            per,
+            show(slope.high),
+            per
+        )?;
+        let changes = change_points(sequence);
+        if changes.is_empty() {
+            writeln!(f, "\tNo change points")?;
+        }
+        let bounds: Vec<usize> = [0]
+            .into_iter()
+            .chain(changes.iter().copied())
+            .chain([sequence.len()])
+            .collect();
+        let segment_mean =
+            |s: usize, t: usize| show(sequence[s..t].iter().sum::<f64>() / (t - s) as f64);
+        for (i, &change) in changes.iter().enumerate() {
+            writeln!(
+                f,
+                "\tChange point before datapoint {}: {} {:.6} -> {:.6}",
+                change + 1,
+                if opt.log { "geometric mean" } else { "mean" },
+                segment_mean(bounds[i], change),
+                segment_mean(change, bounds[i + 2])
+            )?;
+        }
+        if trending || !changes.is_empty() {
+            writeln!(
+                f,
+                "\tWarning: {} is not stationary, so its summary may be misleading",
+                symbol
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{change_points, mann_kendall, sens_slope};
+
+    #[test]
+    fn test_trend() {
+        // Every pair is in order, so S is n(n - 1) / 2
+        let rising: Vec<f64> = (0..20).map(|x| x as f64 * 0.5).collect();
+        let result = mann_kendall(&rising);
+        assert_eq!(190., result.s);
+        assert!(result.p < 1e-6);
+        let slope = sens_slope(&rising, 0.95);
+        assert_eq!(0.5, slope.estimate);
+
+        // Counting pairs directly, with ties
+        let data = [3., 1., 4., 1., 5., 9., 2., 6., 5., 3., 5.];
+        let mut s = 0.;
+        for i in 0..data.len() {
+            for j in i + 1..data.len() {
+                s += ((data[j] > data[i]) as i32 - (data[j] < data[i]) as i32) as f64;
+            }
+        }
+        let result = mann_kendall(&data);
+        assert_eq!(s, result.s);
+        // Ties of 2 (1s and 3s) and 3 (5s): 11 * 10 * 27 - 2 * 18 - 66
+        assert_eq!((2970. - 36. - 66.) / 18., result.var);
+
+        // A step, with alternating noise to estimate the variance from
+        let step: Vec<f64> = (0..60)
+            .map(|i| if i < 40 { 10. } else { 15. } + if i % 2 == 0 { 0.5 } else { -0.5 })
+            .collect();
+        assert_eq!(vec![40], change_points(&step));
+        let flat: Vec<f64> = step.iter().take(40).copied().collect();
+        assert!(change_points(&flat).is_empty());
+    }
+}
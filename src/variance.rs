@@ -0,0 +1,121 @@
+use std::io::Write;
+
+use anyhow::Error;
+
+use crate::anova::{one_way_anova, FTest};
+use crate::args::Opt;
+use crate::dist::f_cdf;
+use crate::stats::{quantile, Stats};
+use crate::t_table::T_CONFIDENCES;
+
+/// Two-sided F-test of whether `other`'s variance differs from the
+/// baseline's, with F the ratio of the variances
+pub fn f_test(baseline: &Stats, other: &Stats) -> FTest {
+    let f = other.var / baseline.var;
+    let (df1, df2) = ((other.n - 1) as f64, (baseline.n - 1) as f64);
+    let lower = f_cdf(f, df1, df2);
+    FTest {
+        f,
+        df1,
+        df2,
+        p: (2. * lower.min(1. - lower)).min(1.),
+    }
+}
+
+/// Levene's test of equal variances: an ANOVA of the absolute deviations of
+/// each sorted dataset from its centre, the mean for Levene's original test
+/// or the median for the Brown-Forsythe variant
+pub fn levene(data: &[&[f64]], median: bool) -> FTest {
+    let deviations: Vec<Stats> = data
+        .iter()
+        .map(|d| {
+            let centre = if median {
+                quantile(d, 0.5)
+            } else {
+                d.iter().sum::<f64>() / d.len() as f64
+            };
+            let mut deviations: Vec<f64> = d.iter().map(|x| (x - centre).abs()).collect();
+            deviations.sort_unstable_by(|a, b| a.total_cmp(b));
+            Stats::from_dataset(&deviations)
+        })
+        .collect();
+    one_way_anova(&deviations)
+}
+
+pub fn print_variance<W, T>(
+    f: &mut W,
+    stats: &[Stats],
+    data: &[T],
+    baseline: usize,
+    opt: &Opt,
+    symbols: &[char],
+) -> Result<(), Error>
+where
+    W: Write,
+    T: AsRef<[f64]>,
+{
+    let confidence_label = T_CONFIDENCES[opt.confidence_level.0];
+    let alpha = 1. - opt.confidence_level.level();
+    let baseline_symbol = symbols[baseline + 1];
+    let mark = |p: f64| if p < alpha { " *" } else { "" };
+    for (idx, (&symbol, other)) in symbols.iter().skip(1).zip(stats).enumerate() {
+        if idx == baseline {
+            continue;
+        }
+        let pair = [data[baseline].as_ref(), data[idx].as_ref()];
+        writeln!(
+            f,
+            "{} Spread compared to {} (* significant at {}% confidence)",
+            symbol, baseline_symbol, confidence_label
+        )?;
+        let result = f_test(&stats[baseline], other);
+        writeln!(
+            f,
+            "\tVariance ratio:  F({}, {}) = {:.6}, p = {:.6}{}",
+            result.df1,
+            result.df2,
+            result.f,
+            result.p,
+            mark(result.p)
+        )?;
+        for (name, median) in [("Levene:", false), ("Brown-Forsythe:", true)] {
+            let result = levene(&pair, median);
+            writeln!(
+                f,
+                "\t{:<16} F({}, {}) = {:.6}, p = {:.6}{}",
+                name,
+                result.df1,
+                result.df2,
+                result.f,
+                result.p,
+                mark(result.p)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{f_test, levene};
+    use crate::stats::Stats;
+
+    #[test]
+    fn test_variance_tests() {
+        let a = [1., 2., 3., 4., 5.];
+        let b = [-1., 1., 3., 5., 7.];
+        let result = f_test(&Stats::from_dataset(&a), &Stats::from_dataset(&b));
+        assert_eq!(4., result.f);
+        assert_eq!((4., 4.), (result.df1, result.df2));
+        // 2 * pf(4, 4, 4, lower.tail = FALSE)
+        assert!((result.p - 0.2080000).abs() < 1e-6);
+
+        // Absolute deviations from the mean of 3 are [2, 1, 0, 1, 2] and
+        // [4, 2, 0, 2, 4], with means 1.2 and 2.4 and variances 0.7 and 2.8
+        let result = levene(&[&a, &b], false);
+        let f = 5. * 2. * 0.6f64.powi(2) / ((0.7 + 2.8) / 2.);
+        assert!((result.f - f).abs() < 1e-12);
+        // The median is also 3, so Brown-Forsythe agrees here
+        assert!((levene(&[&a, &b], true).f - f).abs() < 1e-12);
+    }
+}